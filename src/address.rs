@@ -0,0 +1,284 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # I²C slave addresses
+//!
+//! Most devices are addressed with a 7 bit address. Some devices use a 10 bit address instead. Such a device is
+//! addressed with the reserved 7 bit prefix ``11110xx`` carrying the two upper address bits, followed by the lower
+//! 8 bits of the address as the first byte written. A read from a 10 bit device writes the full address first and
+//! continues with a repeated START condition and the prefix only.
+//!
+//! The 7 bit addresses 0x00 to 0x07 and 0x78 to 0x7F are reserved by the I²C specification for special purposes like
+//! the general call or the 10 bit address prefix. A [SevenBitAddress] is checked to be within the 7 bit range and
+//! tells whether it is such a reserved address. Datasheets often give the address in 8 bit notation including the
+//! R/W bit, which is converted with [SevenBitAddress::from_shifted].
+//!
+//! # Example
+//! ```
+//! # use ruspiro_i2c::*;
+//! let addr = SevenBitAddress::from_shifted(0xD0);
+//! assert_eq!(addr.value(), 0x68);
+//! assert!(!addr.is_reserved());
+//! assert_eq!(SevenBitAddress::new(0x00).unwrap().reserved(), Some(Reserved::GeneralCall));
+//! assert!(SevenBitAddress::new(0x80).is_err());
+//! ```
+//!
+//! A 10 bit device sees the transfer once it has been selected by the lower address bits. A device missing at the
+//! address is reported with the full address, and the index of a data byte it rejects does not count the address
+//! bytes:
+//! ```
+//! # use ruspiro_i2c::*;
+//! # use ruspiro_i2c::backend::sim::*;
+//! struct WriteProtected(SimRegisterDevice);
+//!
+//! impl SimDevice for WriteProtected {
+//!     fn start(&mut self, read: bool) -> bool {
+//!         self.0.start(read)
+//!     }
+//!     fn write(&mut self, data: u8) -> bool {
+//!         // the register pointer is the only byte accepted
+//!         data == 0x10
+//!     }
+//!     fn read(&mut self) -> u8 {
+//!         self.0.read()
+//!     }
+//! }
+//!
+//! let device = SimRegisterDevice::new(256);
+//! let recorder = SimRecorder::new(device.clone());
+//! let bsc = SimulatedBsc::new();
+//! bsc.attach(Address::TenBit(0x2A5), recorder.clone());
+//! bsc.attach(Address::TenBit(0x2A6), WriteProtected(device.clone()));
+//! let mut i2c = I2cImpl::with_backend(bsc);
+//! i2c.initialize(250_000_000, true).unwrap();
+//!
+//! i2c.write_register_u8(Address::TenBit(0x2A5), 0x10, 0x42).unwrap();
+//! let mut buffer = [0; 1];
+//! i2c.write_read(Address::TenBit(0x2A5), &[0x10], &mut buffer).unwrap();
+//! assert_eq!(buffer, [0x42]);
+//! assert_eq!(
+//!     recorder.events(),
+//!     [
+//!         SimEvent::Start { read: false },
+//!         SimEvent::Write(0x10),
+//!         SimEvent::Write(0x42),
+//!         SimEvent::Stop,
+//!         SimEvent::Start { read: false },
+//!         SimEvent::Write(0x10),
+//!         SimEvent::Start { read: true },
+//!         SimEvent::Read(0x42),
+//!         SimEvent::Stop,
+//!     ]
+//! );
+//!
+//! // the prefix is acknowledged by the devices sharing the upper bits, the lower bits are not
+//! let missing = Address::TenBit(0x2A7);
+//! assert_eq!(i2c.write_register_u8(missing, 0x10, 1), Err(I2cError::AddressNack { addr: missing }));
+//! assert_eq!(i2c.write_read(missing, &[0x10], &mut buffer), Err(I2cError::AddressNack { addr: missing }));
+//! // no device shares the upper bits, so the prefix is not acknowledged
+//! let missing = Address::TenBit(0x1A5);
+//! assert_eq!(i2c.write_register_u8(missing, 0x10, 1), Err(I2cError::AddressNack { addr: missing }));
+//!
+//! let protected = Address::TenBit(0x2A6);
+//! assert_eq!(
+//!     i2c.write_register_u8(protected, 0x10, 1),
+//!     Err(I2cError::DataNack { addr: protected, index: 1 })
+//! );
+//! assert_eq!(i2c.write_read(protected, &[0x10], &mut buffer), Ok(1));
+//! ```
+//!
+
+use core::convert::TryFrom;
+use core::fmt;
+
+use crate::{I2cError, I2cResult};
+
+pub(crate) const TEN_BIT_PREFIX: u8 = 0x78; // the reserved 7 bit address 11110xx introducing a 10 bit address
+
+/// The purpose a reserved 7 bit address is set aside for by the I²C specification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reserved {
+  /// 0x00 general call address and START byte
+  GeneralCall,
+  /// 0x01 CBUS address
+  Cbus,
+  /// 0x02 reserved for a different bus format
+  OtherBus,
+  /// 0x03 reserved for future purposes
+  Future,
+  /// 0x04 to 0x07 high speed mode master codes
+  HighSpeedMaster,
+  /// 0x78 to 0x7B prefix of a 10 bit address
+  TenBitPrefix,
+  /// 0x7C to 0x7F device ID
+  DeviceId,
+}
+
+/// A 7 bit slave address that is known to be within the range 0x00 to 0x7F
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SevenBitAddress(u8);
+
+impl SevenBitAddress {
+  /// The general call address all devices supporting the general call listen to
+  pub const GENERAL_CALL: SevenBitAddress = SevenBitAddress(0x00);
+  /// The address the Device ID of a device is read from
+  pub const DEVICE_ID: SevenBitAddress = SevenBitAddress(0x7C);
+
+  /// Create the address, fails with [I2cError::InvalidAddress] if it exceeds 0x7F
+  pub fn new(addr: u8) -> I2cResult<Self> {
+    if addr <= 0x7F {
+      Ok(SevenBitAddress(addr))
+    } else {
+      Err(I2cError::InvalidAddress {
+        addr: Address::SevenBit(addr),
+      })
+    }
+  }
+
+  /// Create the address from the 8 bit notation used by some datasheets, where the address is shifted left by one
+  /// bit to make room for the R/W bit. The R/W bit is ignored.
+  pub const fn from_shifted(addr: u8) -> Self {
+    SevenBitAddress(addr >> 1)
+  }
+
+  /// The address value
+  pub const fn value(self) -> u8 {
+    self.0
+  }
+
+  /// The address in 8 bit notation with the R/W bit set for a read
+  pub const fn shifted(self, read: bool) -> u8 {
+    self.0 << 1 | read as u8
+  }
+
+  /// The purpose this address is reserved for, or `None` if it is a regular slave address
+  pub fn reserved(self) -> Option<Reserved> {
+    match self.0 {
+      0x00 => Some(Reserved::GeneralCall),
+      0x01 => Some(Reserved::Cbus),
+      0x02 => Some(Reserved::OtherBus),
+      0x03 => Some(Reserved::Future),
+      0x04..=0x07 => Some(Reserved::HighSpeedMaster),
+      0x78..=0x7B => Some(Reserved::TenBitPrefix),
+      0x7C..=0x7F => Some(Reserved::DeviceId),
+      _ => None,
+    }
+  }
+
+  /// Whether this address is reserved and not used by a regular slave device
+  pub fn is_reserved(self) -> bool {
+    self.reserved().is_some()
+  }
+}
+
+impl TryFrom<u8> for SevenBitAddress {
+  type Error = I2cError;
+
+  fn try_from(addr: u8) -> I2cResult<Self> {
+    SevenBitAddress::new(addr)
+  }
+}
+
+impl From<SevenBitAddress> for u8 {
+  fn from(addr: SevenBitAddress) -> Self {
+    addr.0
+  }
+}
+
+impl From<SevenBitAddress> for Address {
+  fn from(addr: SevenBitAddress) -> Self {
+    Address::SevenBit(addr.0)
+  }
+}
+
+impl fmt::Display for SevenBitAddress {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "0x{:02X}", self.0)
+  }
+}
+
+/// The address of an I²C slave device. A plain `u8` and a [SevenBitAddress] convert into a 7 bit address. A plain
+/// `u8` is checked to be a valid 7 bit address once the device is accessed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Address {
+  /// A 7 bit address in the range 0x00 to 0x7F
+  SevenBit(u8),
+  /// A 10 bit address in the range 0x000 to 0x3FF
+  TenBit(u16),
+}
+
+impl Address {
+  /// Whether this is a 10 bit address
+  pub fn is_ten_bit(self) -> bool {
+    matches!(self, Address::TenBit(_))
+  }
+
+  /// Check that the address fits into its address range
+  pub(crate) fn validate(self) -> I2cResult<()> {
+    let valid = match self {
+      Address::SevenBit(addr) => SevenBitAddress::new(addr).is_ok(),
+      Address::TenBit(addr) => addr <= 0x3FF,
+    };
+    if valid {
+      Ok(())
+    } else {
+      Err(I2cError::InvalidAddress { addr: self })
+    }
+  }
+
+  /// The value of the slave address register. This is the 10 bit prefix with the upper address bits for a 10 bit
+  /// address.
+  pub(crate) fn header(self) -> u8 {
+    match self {
+      Address::SevenBit(addr) => addr,
+      Address::TenBit(addr) => TEN_BIT_PREFIX | (addr >> 8) as u8 & 0x03,
+    }
+  }
+
+  /// The lower 8 bits of a 10 bit address that are written as first byte of each write to the device
+  pub(crate) fn low_byte(self) -> Option<u8> {
+    match self {
+      Address::SevenBit(_) => None,
+      Address::TenBit(addr) => Some(addr as u8),
+    }
+  }
+
+  /// Replace the address prefix in the error of a transfer to a 10 bit device with the full address. The
+  /// ``prefixes`` are the positions of the lower address bytes within all bytes written by the transfer, so a
+  /// rejected lower address byte is reported as rejected address and the index of a rejected data byte does not
+  /// count the address bytes.
+  pub(crate) fn map_error(self, prefixes: &[usize], error: I2cError) -> I2cError {
+    match error {
+      I2cError::AddressNack { .. } => I2cError::AddressNack { addr: self },
+      I2cError::DataNack { index, .. } if prefixes.contains(&index) => {
+        I2cError::AddressNack { addr: self }
+      }
+      I2cError::DataNack { index, .. } => I2cError::DataNack {
+        addr: self,
+        index: index - prefixes.iter().filter(|&&prefix| prefix < index).count(),
+      },
+      I2cError::ClockStretchTimeout { .. } => I2cError::ClockStretchTimeout { addr: self },
+      I2cError::Timeout { .. } => I2cError::Timeout { addr: self },
+      error => error,
+    }
+  }
+}
+
+impl From<u8> for Address {
+  fn from(addr: u8) -> Self {
+    Address::SevenBit(addr)
+  }
+}
+
+impl fmt::Display for Address {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match *self {
+      Address::SevenBit(addr) => write!(f, "0x{:02X}", addr),
+      Address::TenBit(addr) => write!(f, "0x{:03X} (10 bit)", addr),
+    }
+  }
+}
@@ -0,0 +1,201 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Asynchronous transfers
+//!
+//! The futures of the asynchronous transfers service the controller FIFO each time they are polled and complete once
+//! the controller has finished the transfer. While the transfer is in progress the task is woken either immediately
+//! to be polled again or, if enabled, from the interrupt raised by the BSC controller. The waker of the task is kept
+//! in a cell per controller that is updated with atomic operations only, so the interrupt handler never waits for the
+//! task registering its waker.
+//!
+//! A transfer fails with [I2cError::Timeout] once it takes longer than the timeout of the bus. The timeout is checked
+//! each time the future is polled, so a task woken from the interrupt relies on the controller raising its interrupt
+//! once the device stretches the clock longer than the clock stretch timeout.
+//!
+//! Dropping the future of a transfer that has not completed yet aborts the transfer on the controller, so the bus is
+//! ready for the next transfer right away.
+//!
+
+extern crate alloc;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::future::poll_fn;
+use core::sync::atomic::{AtomicU8, Ordering};
+use core::task::{Poll, Waker};
+
+use crate::backend::{control, BscBackend, BscRegister, MmioBackend};
+use crate::interface::{self, Timing};
+use crate::irq::{self, IrqTransfer, Phase};
+use crate::{Address, Bsc, I2cError, I2cResult};
+
+/// Process the transfer made of the given phases. If ``wake`` is set to the BSC controller of the backend the task
+/// is woken from the interrupt of this controller, otherwise it's woken immediately to be polled again. Returns the
+/// data read by all read phases.
+pub(crate) async fn transfer<B: BscBackend>(
+  bsc: &B,
+  timing: &Timing,
+  wake: Option<Bsc>,
+  addr: Address,
+  phases: Vec<Phase>,
+) -> I2cResult<Vec<u8>> {
+  let mut transfer = IrqTransfer::start(bsc, addr, phases, wake.is_some())?;
+  let mut guard = AbortOnDrop {
+    bsc,
+    wake,
+    active: true,
+  };
+  let deadline = bsc.now() + transfer.timeout(timing);
+  let result = poll_fn(|cx| {
+    transfer.service(bsc);
+    if let Some(result) = transfer.take_result() {
+      return Poll::Ready(result);
+    }
+    if bsc.now() > deadline {
+      // abort the transfer still active on the controller
+      bsc.write(BscRegister::Control, control::FIFO_CLR);
+      if timing.recover {
+        // the timeout is reported even if the recovery has released the bus
+        let _ = interface::recover_bus(bsc, timing);
+      }
+      return Poll::Ready(Err(I2cError::Timeout { addr }));
+    }
+    match wake {
+      Some(controller) => {
+        // the waker need to be known before the interrupt could be raised again
+        controller_waker(controller).register(cx.waker());
+        irq::unmask_interrupts(bsc);
+      }
+      None => cx.waker().wake_by_ref(),
+    }
+    Poll::Pending
+  })
+  .await;
+  guard.active = false;
+  result
+}
+
+/// Abort the transfer still active on the controller once the future processing it is dropped before completion
+struct AbortOnDrop<'a, B: BscBackend> {
+  bsc: &'a B,
+  wake: Option<Bsc>,
+  active: bool,
+}
+
+impl<B: BscBackend> Drop for AbortOnDrop<'_, B> {
+  fn drop(&mut self) {
+    if !self.active {
+      return;
+    }
+    // disabling the controller aborts the transfer and masks its interrupts
+    self.bsc.write(BscRegister::Control, control::FIFO_CLR);
+    interface::clear_status(self.bsc);
+    if let Some(controller) = self.wake {
+      // the task of the dropped future is not woken from the interrupt any more
+      controller_waker(controller).take();
+    }
+  }
+}
+
+/// Wake the task waiting for the interrupt of the BSC controller. The interrupts of the controller are disabled until
+/// the task has serviced the controller.
+pub(crate) fn wake_task(bsc: Bsc) {
+  if let Some(waker) = controller_waker(bsc).take() {
+    irq::mask_interrupts(&MmioBackend::new(bsc));
+    waker.wake();
+  }
+}
+
+static BSC0_WAKER: WakerCell = WakerCell::new();
+static BSC1_WAKER: WakerCell = WakerCell::new();
+#[cfg(feature = "ruspiro_pi4")]
+static BSC3_WAKER: WakerCell = WakerCell::new();
+#[cfg(feature = "ruspiro_pi4")]
+static BSC4_WAKER: WakerCell = WakerCell::new();
+#[cfg(feature = "ruspiro_pi4")]
+static BSC5_WAKER: WakerCell = WakerCell::new();
+#[cfg(feature = "ruspiro_pi4")]
+static BSC6_WAKER: WakerCell = WakerCell::new();
+
+/// The cell of the waker of the task waiting for the interrupt of a BSC controller
+fn controller_waker(bsc: Bsc) -> &'static WakerCell {
+  match bsc {
+    Bsc::Bsc0 => &BSC0_WAKER,
+    Bsc::Bsc1 => &BSC1_WAKER,
+    #[cfg(feature = "ruspiro_pi4")]
+    Bsc::Bsc3 => &BSC3_WAKER,
+    #[cfg(feature = "ruspiro_pi4")]
+    Bsc::Bsc4 => &BSC4_WAKER,
+    #[cfg(feature = "ruspiro_pi4")]
+    Bsc::Bsc5 => &BSC5_WAKER,
+    #[cfg(feature = "ruspiro_pi4")]
+    Bsc::Bsc6 => &BSC6_WAKER,
+  }
+}
+
+// no task is registering or waking the waker
+const IDLE: u8 = 0;
+// a task is registering its waker
+const REGISTERING: u8 = 1;
+// the interrupt handler is waking the task
+const WAKING: u8 = 2;
+
+/// A waker shared between the task and the interrupt handler. A wake while the task is registering its waker is
+/// passed on to the task, which wakes itself once the waker is registered.
+struct WakerCell {
+  state: AtomicU8,
+  waker: UnsafeCell<Option<Waker>>,
+}
+
+// the waker is only accessed by the side that has set the state from IDLE
+unsafe impl Sync for WakerCell {}
+
+impl WakerCell {
+  const fn new() -> Self {
+    WakerCell {
+      state: AtomicU8::new(IDLE),
+      waker: UnsafeCell::new(None),
+    }
+  }
+
+  /// Register the waker of the task to be woken from the interrupt
+  fn register(&self, waker: &Waker) {
+    match self
+      .state
+      .compare_exchange(IDLE, REGISTERING, Ordering::Acquire, Ordering::Acquire)
+    {
+      Ok(_) => {
+        unsafe { *self.waker.get() = Some(waker.clone()) };
+        if self
+          .state
+          .compare_exchange(REGISTERING, IDLE, Ordering::AcqRel, Ordering::Acquire)
+          .is_err()
+        {
+          // the interrupt has been raised while registering, so the task is woken right away
+          let waker = unsafe { (*self.waker.get()).take() };
+          self.state.store(IDLE, Ordering::Release);
+          if let Some(waker) = waker {
+            waker.wake();
+          }
+        }
+      }
+      // the interrupt handler is waking the previous waker, which need to be the same task
+      Err(_) => waker.wake_by_ref(),
+    }
+  }
+
+  /// Take the waker registered by the task. Returns ``None`` if there is no waker or the task is registering its
+  /// waker at the moment, which wakes the task once it's registered.
+  fn take(&self) -> Option<Waker> {
+    if self.state.fetch_or(WAKING, Ordering::AcqRel) != IDLE {
+      return None;
+    }
+    let waker = unsafe { (*self.waker.get()).take() };
+    self.state.fetch_and(!WAKING, Ordering::Release);
+    waker
+  }
+}
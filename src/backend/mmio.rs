@@ -0,0 +1,58 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Memory mapped BSC controller
+//!
+
+use super::{BscBackend, BscRegister};
+use crate::controller::{Bsc, BscPins};
+
+/// A BSC controller of the Raspberry Pi accessed through its memory mapped registers
+#[derive(Debug)]
+pub struct MmioBackend {
+  bsc: Bsc,
+  base: usize,
+}
+
+impl MmioBackend {
+  /// Create the backend accessing the given BSC controller
+  pub const fn new(bsc: Bsc) -> Self {
+    MmioBackend {
+      bsc,
+      base: bsc.base(),
+    }
+  }
+
+  /// The BSC controller accessed by this backend
+  pub fn bsc(&self) -> Bsc {
+    self.bsc
+  }
+}
+
+impl Default for MmioBackend {
+  fn default() -> Self {
+    Self::new(Bsc::Bsc1)
+  }
+}
+
+impl BscBackend for MmioBackend {
+  fn read(&self, reg: BscRegister) -> u32 {
+    unsafe { core::ptr::read_volatile((self.base + reg.offset()) as *const u32) }
+  }
+
+  fn write(&self, reg: BscRegister, value: u32) {
+    unsafe { core::ptr::write_volatile((self.base + reg.offset()) as *mut u32, value) }
+  }
+
+  fn pins(&self) -> Option<BscPins> {
+    Some(self.bsc.pins())
+  }
+
+  fn controller(&self) -> Option<Bsc> {
+    Some(self.bsc)
+  }
+}
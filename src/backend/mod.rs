@@ -0,0 +1,146 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # BSC controller access
+//!
+//! The I²C bus is driven by the Broadcom Serial Controller (BSC) peripheral. Every access to its registers is done
+//! through the [BscBackend] trait. The default backend is the memory mapped hardware controller [MmioBackend]. The
+//! [sim::SimulatedBsc] is a software model of the controller that allows to run the I²C bus implementation against
+//! simulated slave devices on any host machine. The [SoftBsc] emulates the controller by bit-banging two arbitrary
+//! GPIO pins to drive an additional I²C bus.
+//!
+
+use crate::controller::{Bsc, BscPins};
+use crate::{I2cError, I2cResult};
+use core::time::Duration;
+use ruspiro_timer as timer;
+
+mod mmio;
+pub use mmio::MmioBackend;
+mod model;
+mod soft;
+pub use soft::SoftBsc;
+pub(crate) use soft::{Line, Wire};
+
+pub mod sim;
+
+/// The registers of the BSC controller
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BscRegister {
+  /// control register (C)
+  Control,
+  /// status register (S)
+  Status,
+  /// data length register (DLEN)
+  DataLength,
+  /// slave address register (A)
+  SlaveAddress,
+  /// data FiFo register (FIFO)
+  Fifo,
+  /// clock divisor register (CDIV)
+  ClockDivisor,
+  /// data delay register (DEL)
+  DataDelay,
+  /// clock stretch timeout register (CLKT)
+  ClockStretchTimeout,
+}
+
+impl BscRegister {
+  /// The offset of the register from the base address of the controller
+  pub const fn offset(self) -> usize {
+    match self {
+      BscRegister::Control => 0x00,
+      BscRegister::Status => 0x04,
+      BscRegister::DataLength => 0x08,
+      BscRegister::SlaveAddress => 0x0C,
+      BscRegister::Fifo => 0x10,
+      BscRegister::ClockDivisor => 0x14,
+      BscRegister::DataDelay => 0x18,
+      BscRegister::ClockStretchTimeout => 0x1C,
+    }
+  }
+}
+
+/// Bits of the control register
+pub mod control {
+  /// I²C bus enabled flag
+  pub const ENABLE: u32 = 1 << 15;
+  /// Receive interrupt flag
+  pub const IRQ_RX: u32 = 1 << 10;
+  /// Transmit interrupt flag
+  pub const IRQ_TX: u32 = 1 << 9;
+  /// Done interrupt flag
+  pub const IRQ_DONE: u32 = 1 << 8;
+  /// Start transfer flag
+  pub const STARTTRANS: u32 = 1 << 7;
+  /// clear fifo buffer
+  pub const FIFO_CLR: u32 = 1 << 4;
+  /// 1 Read / 0 Write operation
+  pub const READ: u32 = 1 << 0;
+}
+
+/// Bits of the status register
+pub mod status {
+  /// 1 Slave has held the SCL signal longer than allowed high
+  pub const CLK_TIMEOUT: u32 = 1 << 9;
+  /// 1 Slave address acknowledge error
+  pub const ACK_ERROR: u32 = 1 << 8;
+  /// 1 FIFO is full
+  pub const RX_FULL: u32 = 1 << 7;
+  /// 1 FIFO is empty
+  pub const TX_EMPTY: u32 = 1 << 6;
+  /// 1 FIFO contains at least one byte
+  pub const RX_DATA: u32 = 1 << 5;
+  /// 1 FIFO can accept data
+  pub const TX_DATA: u32 = 1 << 4;
+  /// 1 FIFO is full and needs reading from the FIFO
+  pub const RX_NEEDREAD: u32 = 1 << 3;
+  /// 1 FIFO is less than full and needs writing to the FIFO
+  pub const TX_NEEDWRITE: u32 = 1 << 2;
+  /// 1 if transfer is complete
+  pub const TRANS_DONE: u32 = 1 << 1;
+  /// 1 if transfer is active
+  pub const TRANS_ACTIVE: u32 = 1 << 0;
+}
+
+/// Access to the registers of a BSC controller
+pub trait BscBackend {
+  /// Read the current value of the given register
+  fn read(&self, reg: BscRegister) -> u32;
+
+  /// Write the value to the given register
+  fn write(&self, reg: BscRegister, value: u32);
+
+  /// The GPIO pins the controller is connected to. Those pins are reserved for exclusive use once the I²C bus is
+  /// initialized. A backend that does not drive real hardware returns `None`.
+  fn pins(&self) -> Option<BscPins> {
+    None
+  }
+
+  /// Release a device holding the SDA line low, see [I2cImpl::recover_bus](crate::I2cImpl::recover_bus). This is
+  /// only called for a backend without [BscBackend::pins] driving the bus lines itself. Any other backend is not
+  /// able to recover the bus and fails with [I2cError::RecoveryUnsupported].
+  fn recover_bus(&self) -> I2cResult<()> {
+    Err(I2cError::RecoveryUnsupported)
+  }
+
+  /// The BSC controller whose interrupt is raised for this backend. The interrupt driven transfers of such a backend
+  /// are serviced by [crate::handle_i2c_interrupt]. A backend that does not drive real hardware returns `None`.
+  fn controller(&self) -> Option<Bsc> {
+    None
+  }
+
+  /// Give the controller some time to progress with the current transfer while waiting for it to finish
+  fn idle(&self) {
+    timer::sleepcycles(1000);
+  }
+
+  /// The current time the timeout of a transfer is measured against
+  fn now(&self) -> Duration {
+    timer::now()
+  }
+}
@@ -0,0 +1,348 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # BSC register model
+//!
+//! The registers, the 16 byte deep FIFO and the status flags of the BSC controller modelled in software. Both the
+//! [SimulatedBsc](super::sim::SimulatedBsc) and the [SoftBsc](super::SoftBsc) are built on this model and only
+//! provide the [Bus] the START and STOP conditions and the bytes of a transfer are passed to. The bus progresses one
+//! step (address phase or single byte) each time the status register is read, so the driver sees the same sequence
+//! of flags it would see on the real controller.
+//!
+
+extern crate alloc;
+use alloc::collections::VecDeque;
+
+use super::{control, status, BscRegister};
+
+pub(crate) const FIFO_DEPTH: usize = 16; // the FiFo size of the BSC controller
+const FIFO_NEEDREAD: usize = 12; // FiFo fill level the RXR flag is raised at
+const CDIV_RESET: u32 = 0x05DC; // reset value of the clock divisor register
+const CDIV_ZERO: u32 = 0x8000; // the divisor a clock divisor register value of 0 stands for
+const DEL_RESET: u32 = 0x0030_0030; // reset value of the data delay register
+const CLKT_RESET: u32 = 0x40; // reset value of the clock stretch timeout register
+
+/// The reason a bus operation has not been completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BusError {
+  /// The operation is still in progress, e.g. while a device stretches the clock, and is retried with the next step
+  Busy,
+  /// The device has not acknowledged the address or data byte
+  Nack,
+  /// A device held the clock line longer than the clock stretch timeout allows
+  ClockStretchTimeout,
+}
+
+impl BusError {
+  /// The error flags of the status register raised by this error
+  fn flags(self) -> u32 {
+    match self {
+      BusError::Busy => 0,
+      BusError::Nack => status::ACK_ERROR,
+      BusError::ClockStretchTimeout => status::CLK_TIMEOUT,
+    }
+  }
+}
+
+/// The bus the [BscModel] passes the conditions and bytes of its transfers to
+pub(crate) trait Bus {
+  /// The clock divisor or the clock stretch timeout register has been written. A divisor register value of 0 is
+  /// already replaced by the divisor it stands for.
+  fn configure(&mut self, divisor: u32, clkt: u32);
+
+  /// Issue a START condition, or a repeated START if the bus has not been released since the last transfer, followed
+  /// by the address of the transfer
+  fn start(&mut self, addr: u8, read: bool, repeated: bool) -> Result<(), BusError>;
+
+  /// Write a byte of the active transfer
+  fn write(&mut self, data: u8) -> Result<(), BusError>;
+
+  /// Read a byte of the active transfer and acknowledge it if further bytes are read
+  fn read(&mut self, ack: bool) -> Result<u8, BusError>;
+
+  /// Release the bus with a STOP condition once the last transfer has finished
+  fn stop(&mut self) -> Result<(), BusError>;
+
+  /// Release the bus after the controller has been disabled in the middle of a transfer
+  fn abort(&mut self) -> Result<(), BusError> {
+    self.stop()
+  }
+}
+
+/// A transfer currently processed on the bus
+#[derive(Clone, Copy)]
+struct Transfer {
+  addr: u8,
+  read: bool,
+  addressed: bool,
+}
+
+/// A transfer requested while another one was still active. It is started with a repeated START condition once the
+/// active transfer has finished.
+#[derive(Clone, Copy)]
+struct Pending {
+  addr: u8,
+  len: u32,
+  read: bool,
+}
+
+/// The register state of a BSC controller driving the given bus
+pub(crate) struct BscModel<B> {
+  pub(crate) bus: B,
+  control: u32,
+  status: u32,
+  dlen: u32,
+  remaining: u32,
+  addr: u8,
+  cdiv: u32,
+  delay: u32,
+  clkt: u32,
+  tx: VecDeque<u8>,
+  rx: VecDeque<u8>,
+  transfer: Option<Transfer>,
+  pending: Option<Pending>,
+  /// the bus has been taken with a START condition that has not been followed by a STOP condition yet
+  owned: bool,
+}
+
+impl<B: Bus> BscModel<B> {
+  /// Create the model with the register reset values
+  pub(crate) fn new(bus: B) -> Self {
+    let mut model = BscModel {
+      bus,
+      control: 0,
+      status: 0,
+      dlen: 0,
+      remaining: 0,
+      addr: 0,
+      cdiv: CDIV_RESET,
+      delay: DEL_RESET,
+      clkt: CLKT_RESET,
+      tx: VecDeque::with_capacity(FIFO_DEPTH),
+      rx: VecDeque::with_capacity(FIFO_DEPTH),
+      transfer: None,
+      pending: None,
+      owned: false,
+    };
+    model.configure();
+    model
+  }
+
+  pub(crate) fn read(&mut self, reg: BscRegister) -> u32 {
+    match reg {
+      BscRegister::Control => self.control,
+      BscRegister::Status => {
+        // each status read lets the bus progress by one step
+        self.step();
+        self.status()
+      }
+      BscRegister::DataLength => {
+        if self.transfer.is_some() || self.status & status::TRANS_DONE != 0 {
+          self.remaining
+        } else {
+          self.dlen
+        }
+      }
+      BscRegister::SlaveAddress => self.addr as u32,
+      BscRegister::Fifo => self.rx.pop_front().unwrap_or(0) as u32,
+      BscRegister::ClockDivisor => self.cdiv,
+      BscRegister::DataDelay => self.delay,
+      BscRegister::ClockStretchTimeout => self.clkt,
+    }
+  }
+
+  pub(crate) fn write(&mut self, reg: BscRegister, value: u32) {
+    match reg {
+      BscRegister::Control => self.write_control(value),
+      BscRegister::Status => {
+        // the error and done flags are cleared by writing 1
+        self.status &= !(value & (status::CLK_TIMEOUT | status::ACK_ERROR | status::TRANS_DONE));
+      }
+      BscRegister::DataLength => self.dlen = value & 0xFFFF,
+      BscRegister::SlaveAddress => self.addr = (value & 0x7F) as u8,
+      BscRegister::Fifo => {
+        if self.tx.len() < FIFO_DEPTH {
+          self.tx.push_back(value as u8);
+        }
+      }
+      BscRegister::ClockDivisor => {
+        self.cdiv = value & 0xFFFF;
+        self.configure();
+      }
+      BscRegister::DataDelay => self.delay = value,
+      BscRegister::ClockStretchTimeout => {
+        self.clkt = value & 0xFFFF;
+        self.configure();
+      }
+    }
+  }
+
+  /// Whether the controller raises its interrupt line with the current status
+  pub(crate) fn interrupt_raised(&self) -> bool {
+    let value = self.status();
+    (self.control & control::IRQ_DONE != 0 && value & status::TRANS_DONE != 0)
+      || (self.control & control::IRQ_TX != 0 && value & status::TX_NEEDWRITE != 0)
+      || (self.control & control::IRQ_RX != 0 && value & status::RX_NEEDREAD != 0)
+  }
+
+  /// Process the next step of the active transfer on the bus
+  pub(crate) fn step(&mut self) {
+    let mut transfer = match self.transfer {
+      Some(transfer) => transfer,
+      None => return,
+    };
+
+    let result = if !transfer.addressed {
+      // START condition and address phase
+      match self.bus.start(transfer.addr, transfer.read, self.owned) {
+        Err(BusError::Busy) => return,
+        result => {
+          self.owned = true;
+          transfer.addressed = true;
+          self.transfer = Some(transfer);
+          result
+        }
+      }
+    } else if transfer.read {
+      // the master holds the bus while the FiFo is full
+      if self.rx.len() >= FIFO_DEPTH {
+        return;
+      }
+      // the last byte of a transfer is not acknowledged
+      match self.bus.read(self.remaining > 1) {
+        Err(BusError::Busy) => return,
+        result => result.map(|data| {
+          self.rx.push_back(data);
+          self.remaining -= 1;
+        }),
+      }
+    } else {
+      let data = match self.tx.front() {
+        Some(&data) => data,
+        None => return,
+      };
+      match self.bus.write(data) {
+        Err(BusError::Busy) => return,
+        Err(BusError::ClockStretchTimeout) => Err(BusError::ClockStretchTimeout),
+        result => {
+          // a byte not acknowledged has been transferred nevertheless
+          self.tx.pop_front();
+          self.remaining -= 1;
+          result
+        }
+      }
+    };
+
+    match result {
+      Err(error) => self.finish(error.flags()),
+      Ok(()) if self.remaining == 0 => self.finish(0),
+      Ok(()) => (),
+    }
+  }
+
+  fn write_control(&mut self, value: u32) {
+    if value & (control::FIFO_CLR | control::FIFO_CLR << 1) != 0 {
+      self.tx.clear();
+      self.rx.clear();
+    }
+    self.control = value & !(control::STARTTRANS | control::FIFO_CLR | control::FIFO_CLR << 1);
+    if value & control::ENABLE == 0 {
+      // disabling the controller aborts the active transfer and releases the bus
+      self.transfer = None;
+      self.pending = None;
+      if self.owned {
+        self.owned = false;
+        if let Err(error) = self.bus.abort() {
+          self.status |= error.flags();
+        }
+      }
+      return;
+    }
+    if value & control::STARTTRANS == 0 {
+      return;
+    }
+
+    let read = value & control::READ != 0;
+    if let Some(transfer) = self.transfer {
+      // the controller is only able to continue a write with a read by a repeated START, any other transfer need to
+      // wait until the active one is done
+      debug_assert!(
+        !transfer.read && read,
+        "transfer started while the previous one is still active, only a read may follow a write"
+      );
+      // a start while the bus is still active is executed as repeated START after the current transfer
+      self.pending = Some(Pending {
+        addr: self.addr,
+        len: self.dlen,
+        read,
+      });
+    } else {
+      self.start(self.addr, self.dlen, read);
+    }
+  }
+
+  fn start(&mut self, addr: u8, len: u32, read: bool) {
+    self.remaining = len;
+    self.transfer = Some(Transfer {
+      addr,
+      read,
+      addressed: false,
+    });
+  }
+
+  /// The active transfer has finished, either continue with the pending one or release the bus. A transfer failed
+  /// with the given error flags of the status register.
+  fn finish(&mut self, error: u32) {
+    self.transfer = None;
+    if error != 0 {
+      self.status |= error;
+      self.pending = None;
+    }
+    match self.pending.take() {
+      Some(pending) => self.start(pending.addr, pending.len, pending.read),
+      None => {
+        self.status |= status::TRANS_DONE;
+        self.owned = false;
+        if let Err(error) = self.bus.stop() {
+          self.status |= error.flags();
+        }
+      }
+    }
+  }
+
+  fn configure(&mut self) {
+    let divisor = if self.cdiv == 0 { CDIV_ZERO } else { self.cdiv };
+    self.bus.configure(divisor, self.clkt);
+  }
+
+  /// Calculate the current status register value
+  fn status(&self) -> u32 {
+    let mut value = self.status & (status::CLK_TIMEOUT | status::ACK_ERROR | status::TRANS_DONE);
+    if self.rx.len() == FIFO_DEPTH {
+      value |= status::RX_FULL;
+    }
+    if self.tx.is_empty() {
+      value |= status::TX_EMPTY;
+    }
+    if !self.rx.is_empty() {
+      value |= status::RX_DATA;
+    }
+    if self.tx.len() < FIFO_DEPTH {
+      value |= status::TX_DATA;
+    }
+    if let Some(transfer) = self.transfer {
+      value |= status::TRANS_ACTIVE;
+      if transfer.read && self.rx.len() >= FIFO_NEEDREAD {
+        value |= status::RX_NEEDREAD;
+      }
+      if !transfer.read && self.tx.len() < FIFO_DEPTH && (self.tx.len() as u32) < self.remaining {
+        value |= status::TX_NEEDWRITE;
+      }
+    }
+    value
+  }
+}
@@ -0,0 +1,605 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Simulated BSC controller
+//!
+//! A software model of the BSC controller state machine. It provides the 16 byte deep FIFO, the DONE/ERR/CLKT status
+//! flags and the RXD/TXD/RXR/TXW FIFO flags and processes a transfer against simulated slave devices attached to
+//! it. The bus progresses one step (address phase or single byte) each time the status register is read, so the
+//! driver sees the same sequence of flags it would see on real hardware. The timeouts of the transfers are measured
+//! against a virtual clock advancing with each step of the bus and while the driver is idle. A device stretching the
+//! clock holds the transfer for the SCL clock cycles of the bus, assuming the core clock of 250 MHz. Like the real
+//! controller it continues a write with a read by a repeated START only. Starting any other transfer while one is
+//! still active panics in debug builds, as the real controller would corrupt the transfer in this case.
+//!
+//! # Example
+//! ```
+//! # use ruspiro_i2c::I2cImpl;
+//! # use ruspiro_i2c::backend::sim::*;
+//! let device = SimRegisterDevice::new(256);
+//! let bsc = SimulatedBsc::new();
+//! bsc.attach(0x68, device.clone());
+//!
+//! let mut i2c = I2cImpl::with_backend(bsc);
+//! i2c.initialize(250_000_000, true).unwrap();
+//! i2c.write_register_u8(0x68, 0x10, 1).unwrap();
+//! assert_eq!(device.register(0x10), 1);
+//!
+//! device.set_register(0x11, 2);
+//! let mut buffer = [0; 2];
+//! i2c.read_register_buff(0x68, 0x10, &mut buffer).unwrap();
+//! assert_eq!(buffer, [1, 2]);
+//! assert_eq!(i2c.scan().unwrap(), vec![0x68]);
+//! ```
+//!
+//! Transfers exceeding the FIFO depth are streamed through the FIFO while the transfer is active:
+//! ```
+//! # use ruspiro_i2c::I2cImpl;
+//! # use ruspiro_i2c::backend::sim::*;
+//! let device = SimRegisterDevice::new(1024);
+//! let bsc = SimulatedBsc::new();
+//! bsc.attach(0x50, device.clone());
+//! let mut i2c = I2cImpl::with_backend(bsc);
+//! i2c.initialize(250_000_000, true).unwrap();
+//!
+//! for &len in [0, 1, 15, 16, 17, 64, 1000].iter() {
+//!     let data: Vec<u8> = (0..len).map(|i| (i * 7 + len) as u8).collect();
+//!     i2c.write_register_buff(0x50, 0x00, &data).unwrap();
+//!     let mut buffer = vec![0; len];
+//!     assert_eq!(i2c.read_register_buff(0x50, 0x00, &mut buffer).unwrap(), len);
+//!     assert_eq!(buffer, data);
+//! }
+//! ```
+//!
+//! A transfer exceeding the 65535 bytes of a single transfer is split. The read continuing the first read is started
+//! once the first one is done:
+//! ```
+//! # use ruspiro_i2c::I2cImpl;
+//! # use ruspiro_i2c::backend::sim::*;
+//! let device = SimRegisterDevice::new(256);
+//! for reg in 0..256 {
+//!     device.set_register(reg, reg as u8);
+//! }
+//! let recorder = SimRecorder::new(device);
+//! let bsc = SimulatedBsc::new();
+//! bsc.attach(0x50, recorder.clone());
+//! let mut i2c = I2cImpl::with_backend(bsc);
+//! i2c.initialize(250_000_000, true).unwrap();
+//!
+//! let mut buffer = vec![0; 70_000];
+//! assert_eq!(i2c.read_register_buff(0x50, 0x00, &mut buffer).unwrap(), 70_000);
+//! assert!(buffer.iter().enumerate().all(|(i, &value)| value == i as u8));
+//!
+//! let events = recorder.events();
+//! let conditions: Vec<_> = events
+//!     .iter()
+//!     .filter(|event| !matches!(event, SimEvent::Read(_)))
+//!     .collect();
+//! assert_eq!(
+//!     conditions,
+//!     [
+//!         &SimEvent::Start { read: false },
+//!         &SimEvent::Write(0x00),
+//!         &SimEvent::Start { read: true },
+//!         &SimEvent::Stop,
+//!         &SimEvent::Start { read: true },
+//!         &SimEvent::Stop,
+//!     ]
+//! );
+//! ```
+//!
+//! Interrupt driven transfers are processed by calling the interrupt handler whenever the simulated controller
+//! raises its interrupt:
+//! ```
+//! # use ruspiro_i2c::I2cImpl;
+//! # use ruspiro_i2c::backend::sim::*;
+//! let device = SimRegisterDevice::new(256);
+//! device.set_register(0x20, 0xAB);
+//! let bsc = SimulatedBsc::new();
+//! bsc.attach(0x68, device.clone());
+//! let mut i2c = I2cImpl::with_backend(bsc);
+//! i2c.initialize(250_000_000, true).unwrap();
+//!
+//! i2c.start_write_read(0x68, &[0x20], 1).unwrap();
+//! let data = loop {
+//!     if i2c.backend().interrupt_pending() {
+//!         i2c.handle_interrupt();
+//!     }
+//!     if let Some(result) = i2c.transfer_result() {
+//!         break result.unwrap();
+//!     }
+//! };
+//! assert_eq!(data, vec![0xAB]);
+//! ```
+//!
+//! A device stretching the clock up to the clock stretch timeout on each bit still finishes the transfer in time,
+//! while a device holding the clock line for too long fails the transfer:
+//! ```
+//! # use ruspiro_i2c::*;
+//! # use ruspiro_i2c::backend::sim::*;
+//! struct SlowDevice {
+//!     registers: SimRegisterDevice,
+//!     cycles: u32,
+//! }
+//!
+//! impl SimDevice for SlowDevice {
+//!     fn start(&mut self, read: bool) -> bool {
+//!         self.registers.start(read)
+//!     }
+//!     fn write(&mut self, data: u8) -> bool {
+//!         self.registers.write(data)
+//!     }
+//!     fn read(&mut self) -> u8 {
+//!         self.registers.read()
+//!     }
+//!     fn stretch(&mut self) -> u32 {
+//!         self.cycles
+//!     }
+//! }
+//!
+//! let registers = SimRegisterDevice::new(256);
+//! let slow = |cycles| SlowDevice { registers: registers.clone(), cycles };
+//! let bsc = SimulatedBsc::new();
+//! let mut i2c = I2cImpl::with_backend(bsc);
+//! let config = I2cConfig::new(250_000_000, FAST_MODE)
+//!     .with_clock_stretch_timeout(ClockStretchTimeout::Cycles(64));
+//! i2c.initialize_with_config(config).unwrap();
+//!
+//! // the maximum clock stretching is covered by the timeout derived from the bus timing
+//! i2c.backend().attach(0x50, slow(64));
+//! i2c.write_register_buff(0x50, 0x00, &[0x5A; 32]).unwrap();
+//! assert_eq!(registers.register(0x1F), 0x5A);
+//!
+//! // the controller detects a device exceeding the clock stretch timeout
+//! i2c.backend().attach(0x50, slow(65));
+//! assert_eq!(
+//!     i2c.write_register_u8(0x50, 0x00, 1),
+//!     Err(I2cError::ClockStretchTimeout { addr: Address::SevenBit(0x50) })
+//! );
+//!
+//! // without the clock stretch timeout a device never releasing the clock fails the transfer with a timeout
+//! i2c.set_clock_stretch_timeout(ClockStretchTimeout::Disabled).unwrap();
+//! i2c.backend().attach(0x50, slow(u32::MAX));
+//! assert_eq!(
+//!     i2c.write_register_u8(0x50, 0x00, 1),
+//!     Err(I2cError::Timeout { addr: Address::SevenBit(0x50) })
+//! );
+//!
+//! // the asynchronous transfers time out in the same way
+//! # fn block_on<F: core::future::Future>(future: F) -> F::Output {
+//! #     use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+//! #     fn raw() -> RawWaker {
+//! #         RawWaker::new(core::ptr::null(), &VTABLE)
+//! #     }
+//! #     static VTABLE: RawWakerVTable = RawWakerVTable::new(|_| raw(), |_| (), |_| (), |_| ());
+//! #     let waker = unsafe { Waker::from_raw(raw()) };
+//! #     let mut future = Box::pin(future);
+//! #     loop {
+//! #         if let Poll::Ready(output) = future.as_mut().poll(&mut Context::from_waker(&waker)) {
+//! #             break output;
+//! #         }
+//! #     }
+//! # }
+//! assert_eq!(
+//!     block_on(i2c.write_register_buff_async(0x50, 0x00, &[1])),
+//!     Err(I2cError::Timeout { addr: Address::SevenBit(0x50) })
+//! );
+//! ```
+//!
+
+extern crate alloc;
+use alloc::{boxed::Box, rc::Rc, vec, vec::Vec};
+use core::cell::RefCell;
+use core::time::Duration;
+
+use super::model::{BscModel, Bus, BusError};
+use super::{BscBackend, BscRegister};
+use crate::address::TEN_BIT_PREFIX;
+use crate::Address;
+
+const SIM_STEP_TIME: Duration = Duration::from_micros(1); // virtual time passing with each step of the bus
+const SIM_IDLE_TIME: Duration = Duration::from_micros(10); // virtual time passing while the driver is idle
+const SIM_CORE_SPEED: u64 = 250_000_000; // the core clock the clock divisor is applied to
+
+/// A slave device attached to the [SimulatedBsc]
+pub trait SimDevice {
+  /// The device has been addressed with a START or repeated START condition. Returning `false` does not acknowledge
+  /// the address.
+  fn start(&mut self, read: bool) -> bool {
+    let _ = read;
+    true
+  }
+
+  /// The master has written a byte to the device. Returning `false` does not acknowledge this byte.
+  fn write(&mut self, data: u8) -> bool;
+
+  /// The master reads the next byte from the device
+  fn read(&mut self) -> u8;
+
+  /// The number of SCL clock cycles the device holds the clock line low on each bit of the next byte. The transfer
+  /// fails with a clock stretch timeout if this exceeds the clock stretch timeout of the controller.
+  fn stretch(&mut self) -> u32 {
+    0
+  }
+
+  /// The master has finished the transfer with a STOP condition
+  fn stop(&mut self) {}
+}
+
+/// A simulated device providing a set of 8 Bit registers. The first byte written after a START condition selects the
+/// register, each further byte written or read accesses the current register and auto-increments the register
+/// pointer. The register contents are shared between clones of this device, so a test keeps a clone to inspect the
+/// registers of the device attached to the simulated controller.
+#[derive(Clone)]
+pub struct SimRegisterDevice {
+  inner: Rc<RefCell<RegisterFile>>,
+}
+
+struct RegisterFile {
+  registers: Vec<u8>,
+  pointer: usize,
+  pointer_pending: bool,
+}
+
+impl SimRegisterDevice {
+  /// Create a device with `size` registers all set to 0
+  pub fn new(size: usize) -> Self {
+    SimRegisterDevice {
+      inner: Rc::new(RefCell::new(RegisterFile {
+        registers: vec![0; size],
+        pointer: 0,
+        pointer_pending: false,
+      })),
+    }
+  }
+
+  /// The current value of a register
+  pub fn register(&self, reg: usize) -> u8 {
+    self.inner.borrow().registers[reg]
+  }
+
+  /// Set the value of a register
+  pub fn set_register(&self, reg: usize, value: u8) {
+    self.inner.borrow_mut().registers[reg] = value;
+  }
+}
+
+impl SimDevice for SimRegisterDevice {
+  fn start(&mut self, read: bool) -> bool {
+    self.inner.borrow_mut().pointer_pending = !read;
+    true
+  }
+
+  fn write(&mut self, data: u8) -> bool {
+    let mut file = self.inner.borrow_mut();
+    if file.pointer_pending {
+      file.pointer = data as usize;
+      file.pointer_pending = false;
+    } else {
+      let size = file.registers.len();
+      let pointer = file.pointer % size;
+      file.registers[pointer] = data;
+      file.pointer = (pointer + 1) % size;
+    }
+    true
+  }
+
+  fn read(&mut self) -> u8 {
+    let mut file = self.inner.borrow_mut();
+    let size = file.registers.len();
+    let pointer = file.pointer % size;
+    file.pointer = (pointer + 1) % size;
+    file.registers[pointer]
+  }
+}
+
+/// A bus event seen by a simulated slave device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimEvent {
+  /// The device has been addressed with a START or repeated START condition
+  Start { read: bool },
+  /// The master has written a byte to the device
+  Write(u8),
+  /// The master has read a byte from the device
+  Read(u8),
+  /// The master has finished the transfer with a STOP condition
+  Stop,
+}
+
+/// A simulated device recording the bus events it sees before passing them to the wrapped device. The recorded events
+/// are shared between clones of the recorder, so a test keeps a clone to check the sequence of START and STOP
+/// conditions and bytes transferred.
+pub struct SimRecorder<D> {
+  device: Rc<RefCell<D>>,
+  events: Rc<RefCell<Vec<SimEvent>>>,
+}
+
+impl<D: SimDevice> SimRecorder<D> {
+  /// Record the bus events of the given device
+  pub fn new(device: D) -> Self {
+    SimRecorder {
+      device: Rc::new(RefCell::new(device)),
+      events: Rc::new(RefCell::new(Vec::new())),
+    }
+  }
+
+  /// The bus events recorded so far
+  pub fn events(&self) -> Vec<SimEvent> {
+    self.events.borrow().clone()
+  }
+
+  /// Forget the bus events recorded so far
+  pub fn clear(&self) {
+    self.events.borrow_mut().clear();
+  }
+
+  fn record(&self, event: SimEvent) {
+    self.events.borrow_mut().push(event);
+  }
+}
+
+impl<D> Clone for SimRecorder<D> {
+  fn clone(&self) -> Self {
+    SimRecorder {
+      device: self.device.clone(),
+      events: self.events.clone(),
+    }
+  }
+}
+
+impl<D: SimDevice> SimDevice for SimRecorder<D> {
+  fn start(&mut self, read: bool) -> bool {
+    self.record(SimEvent::Start { read });
+    self.device.borrow_mut().start(read)
+  }
+
+  fn write(&mut self, data: u8) -> bool {
+    self.record(SimEvent::Write(data));
+    self.device.borrow_mut().write(data)
+  }
+
+  fn read(&mut self) -> u8 {
+    let data = self.device.borrow_mut().read();
+    self.record(SimEvent::Read(data));
+    data
+  }
+
+  fn stretch(&mut self) -> u32 {
+    self.device.borrow_mut().stretch()
+  }
+
+  fn stop(&mut self) {
+    self.record(SimEvent::Stop);
+    self.device.borrow_mut().stop();
+  }
+}
+
+/// The software model of a BSC controller
+pub struct SimulatedBsc {
+  state: RefCell<BscModel<SimBus>>,
+}
+
+/// The simulated bus the devices are attached to
+struct SimBus {
+  devices: Vec<(Address, Box<dyn SimDevice>)>,
+  // the device addressed by the active transfer
+  device: Option<usize>,
+  // the 10 bit device selected by the last write until the bus is released
+  selected: Option<usize>,
+  // the 10 bit address prefix has been acknowledged and the next byte written selects the device
+  select: Option<u8>,
+  // the device has been asked whether it stretches the clock on the next byte
+  stretched: bool,
+  // the time the device releases the clock line and whether the clock stretch timeout elapses before
+  release: Option<(Duration, bool)>,
+  // the time of a SCL clock cycle
+  period: Duration,
+  clkt: u32,
+  time: Duration,
+}
+
+impl SimulatedBsc {
+  /// Create a simulated controller with the register reset values and no devices attached
+  pub fn new() -> Self {
+    SimulatedBsc {
+      state: RefCell::new(BscModel::new(SimBus {
+        devices: Vec::new(),
+        device: None,
+        selected: None,
+        select: None,
+        stretched: false,
+        release: None,
+        period: Duration::from_micros(0),
+        clkt: 0,
+        time: Duration::from_micros(0),
+      })),
+    }
+  }
+
+  /// Attach a simulated slave device at the given 7 or 10 bit address. A device already attached at this address is
+  /// replaced.
+  pub fn attach<D: SimDevice + 'static>(&self, addr: impl Into<Address>, device: D) {
+    let addr = addr.into();
+    let bus = &mut self.state.borrow_mut().bus;
+    bus.device = None;
+    bus.selected = None;
+    bus.devices.retain(|(a, _)| *a != addr);
+    bus.devices.push((addr, Box::new(device)));
+  }
+
+  /// Remove the simulated slave device at the given address
+  pub fn detach(&self, addr: impl Into<Address>) {
+    let addr = addr.into();
+    let bus = &mut self.state.borrow_mut().bus;
+    bus.device = None;
+    bus.selected = None;
+    bus.devices.retain(|(a, _)| *a != addr);
+  }
+
+  /// Let the bus progress by one step and check whether the controller raises its interrupt line. This allows to
+  /// drive interrupt driven transfers by calling the interrupt handler while this returns ``true``.
+  pub fn interrupt_pending(&self) -> bool {
+    let mut state = self.state.borrow_mut();
+    state.bus.time += SIM_STEP_TIME;
+    state.step();
+    state.interrupt_raised()
+  }
+}
+
+impl Default for SimulatedBsc {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl BscBackend for SimulatedBsc {
+  fn read(&self, reg: BscRegister) -> u32 {
+    let mut state = self.state.borrow_mut();
+    if reg == BscRegister::Status {
+      state.bus.time += SIM_STEP_TIME;
+    }
+    state.read(reg)
+  }
+
+  fn write(&self, reg: BscRegister, value: u32) {
+    self.state.borrow_mut().write(reg, value);
+  }
+
+  fn idle(&self) {
+    self.state.borrow_mut().bus.time += SIM_IDLE_TIME;
+  }
+
+  fn now(&self) -> Duration {
+    self.state.borrow().bus.time
+  }
+}
+
+impl SimBus {
+  /// The index of the device attached at the address
+  fn position(&self, addr: Address) -> Option<usize> {
+    self.devices.iter().position(|(a, _)| *a == addr)
+  }
+
+  /// Ask the addressed device whether it stretches the clock on the next byte and hold the transfer until it releases
+  /// the clock line
+  fn hold(&mut self, idx: usize) -> Result<(), BusError> {
+    if !self.stretched {
+      self.stretched = true;
+      let cycles = self.devices[idx].1.stretch();
+      if cycles > 0 {
+        // the controller gives up once the clock is held longer than the clock stretch timeout on a single bit
+        let timeout = self.clkt != 0 && cycles > self.clkt;
+        let held = if timeout {
+          self.clkt as u64 + 1
+        } else {
+          cycles as u64 * 9
+        };
+        let period = self.period.as_nanos() as u64;
+        self.release = Some((
+          self.time + Duration::from_nanos(period.saturating_mul(held)),
+          timeout,
+        ));
+      }
+    }
+    match self.release {
+      Some((release, _)) if self.time < release => Err(BusError::Busy),
+      Some((_, true)) => Err(BusError::ClockStretchTimeout),
+      _ => {
+        // the next byte is asked for clock stretching again
+        self.stretched = false;
+        self.release = None;
+        Ok(())
+      }
+    }
+  }
+}
+
+impl Bus for SimBus {
+  fn configure(&mut self, divisor: u32, clkt: u32) {
+    self.period = Duration::from_nanos(divisor as u64 * 1_000_000_000 / SIM_CORE_SPEED);
+    self.clkt = clkt;
+  }
+
+  fn start(&mut self, addr: u8, read: bool, _repeated: bool) -> Result<(), BusError> {
+    let upper = (addr & 0x03) as u16;
+    self.select = None;
+    self.stretched = false;
+    self.release = None;
+    self.device = self.position(Address::SevenBit(addr));
+    let ack = match self.device {
+      Some(idx) => self.devices[idx].1.start(read),
+      None if addr & !0x03 != TEN_BIT_PREFIX => false,
+      None if read => {
+        // a read from a 10 bit device continues the write that has selected the device with a repeated START
+        let devices = &self.devices;
+        self.device = self
+          .selected
+          .filter(|&idx| matches!(devices[idx].0, Address::TenBit(a) if a >> 8 == upper));
+        match self.device {
+          Some(idx) => self.devices[idx].1.start(true),
+          None => false,
+        }
+      }
+      None => {
+        // each 10 bit device with the same upper address bits acknowledges the prefix
+        self.select = Some(addr);
+        self
+          .devices
+          .iter()
+          .any(|(a, _)| matches!(a, Address::TenBit(a) if a >> 8 == upper))
+      }
+    };
+    if ack {
+      Ok(())
+    } else {
+      Err(BusError::Nack)
+    }
+  }
+
+  fn write(&mut self, data: u8) -> Result<(), BusError> {
+    let ack = if let Some(prefix) = self.select.take() {
+      // the first byte written carries the lower bits of the 10 bit address
+      self.device = self.position(Address::TenBit((prefix as u16 & 0x03) << 8 | data as u16));
+      self.selected = self.device;
+      match self.device {
+        Some(idx) => self.devices[idx].1.start(false),
+        None => false,
+      }
+    } else {
+      // the device index is always valid once the address has been acknowledged
+      let idx = self.device.unwrap_or(0);
+      self.hold(idx)?;
+      self.devices[idx].1.write(data)
+    };
+    if ack {
+      Ok(())
+    } else {
+      Err(BusError::Nack)
+    }
+  }
+
+  fn read(&mut self, _ack: bool) -> Result<u8, BusError> {
+    let idx = self.device.unwrap_or(0);
+    self.hold(idx)?;
+    Ok(self.devices[idx].1.read())
+  }
+
+  fn stop(&mut self) -> Result<(), BusError> {
+    self.selected = None;
+    if let Some(idx) = self.device.take() {
+      self.devices[idx].1.stop();
+    }
+    Ok(())
+  }
+
+  fn abort(&mut self) -> Result<(), BusError> {
+    // the devices do not see a STOP condition of an aborted transfer
+    self.selected = None;
+    self.device = None;
+    Ok(())
+  }
+}
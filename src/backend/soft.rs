@@ -0,0 +1,354 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Software BSC controller
+//!
+//! A BSC controller emulated in software driving the I²C bus by bit-banging two arbitrary GPIO pins. It shares the
+//! model of the registers, FIFO and status flags with the [SimulatedBsc](super::sim::SimulatedBsc), so the whole
+//! API of [I2cImpl](crate::I2cImpl) is available on busses not connected to one of the BSC controllers. The bus
+//! progresses one step (address phase or single byte) each time the status register is read.
+//!
+//! The pins are driven open drain: a line is pulled low by switching the pin to an output driving low and released
+//! by switching it back to an input with the pull-up enabled. The bus speed is derived from the clock divisor
+//! register. A slave is allowed to stretch the clock for as many SCL clock cycles as the clock stretch timeout
+//! register allows, a value of 0 disables this timeout. The data delay register is not used by the software
+//! controller. As it can not raise an interrupt, interrupt driven transfers require to call
+//! [I2cImpl::handle_interrupt](crate::I2cImpl::handle_interrupt) periodically.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_i2c::*;
+//! # use ruspiro_i2c::backend::SoftBsc;
+//! # fn doc() {
+//!     // the clock divisor is calculated based on the core clock speed passed to the software controller
+//!     let bsc = SoftBsc::new(17, 27, 250_000_000).unwrap();
+//!     let mut i2c = SoftI2c::with_backend(bsc);
+//!     i2c.initialize_with_config(I2cConfig::new(250_000_000, 50_000)).unwrap();
+//!     let value = i2c.read_register_u8(0x68, 0x20).unwrap();
+//! # }
+//! ```
+//!
+
+use core::cell::RefCell;
+use core::time::Duration;
+use ruspiro_gpio::{
+  function::{Input, Output},
+  pud::PullUp,
+  Pin, GPIO,
+};
+use ruspiro_timer as timer;
+
+use super::model::{BscModel, Bus, BusError};
+use super::{BscBackend, BscRegister};
+use crate::{I2cError, I2cResult};
+
+/// A BSC controller emulated in software on two GPIO pins
+pub struct SoftBsc {
+  sda: u32,
+  scl: u32,
+  state: RefCell<BscModel<Wire>>,
+}
+
+/// A GPIO pin driven open drain. The line is released by switching the pin to an input with the pull-up resistor
+/// and only ever actively driven low.
+pub(crate) enum Line {
+  Released(Pin<Input, PullUp>),
+  Low(Pin<Output, PullUp>),
+}
+
+impl Line {
+  pub(crate) fn release(self) -> Self {
+    match self {
+      Line::Low(pin) => {
+        // keep the output latch low while the pin is an input, so the pin drives the line low right away once
+        // switched to an output again instead of glitching high
+        pin.low();
+        Line::Released(pin.into_input())
+      }
+      line => line,
+    }
+  }
+
+  pub(crate) fn pull_low(self) -> Self {
+    match self {
+      Line::Released(pin) => {
+        let pin = pin.into_output();
+        pin.low();
+        Line::Low(pin)
+      }
+      line => line,
+    }
+  }
+
+  pub(crate) fn is_high(&self) -> bool {
+    match self {
+      Line::Released(pin) => pin.is_high(),
+      Line::Low(_) => false,
+    }
+  }
+}
+
+const RECOVERY_CLOCKS: u32 = 9; // max SCL clocks issued to release a stuck SDA line
+
+/// The bit level access to the bus lines
+pub(crate) struct Wire {
+  sda: Option<Line>,
+  scl: Option<Line>,
+  core_speed: u32,
+  half_period: Duration,
+  stretch_limit: u32,
+}
+
+impl Wire {
+  /// Drive the bus on the given lines with the SCL clock period of twice the half period
+  pub(crate) fn new(sda: Line, scl: Line, half_period: Duration) -> Self {
+    Wire {
+      sda: Some(sda),
+      scl: Some(scl),
+      core_speed: 0,
+      half_period,
+      stretch_limit: 0,
+    }
+  }
+
+  /// Clock SCL up to nine times until a device holding the SDA line low releases it, followed by a STOP condition.
+  /// Returns whether SDA has been released.
+  pub(crate) fn recover(&mut self) -> bool {
+    // clock out the bits the device still wants to send until it releases SDA
+    self.delay();
+    for _ in 0..RECOVERY_CLOCKS {
+      if self.sda_is_high() {
+        break;
+      }
+      self.scl_low();
+      self.delay();
+      self.scl_release();
+      self.delay();
+    }
+
+    // generate a STOP condition: SDA rises while SCL is high
+    self.scl_low();
+    self.delay();
+    self.set_sda(false);
+    self.delay();
+    self.scl_release();
+    self.delay();
+    self.set_sda(true);
+    self.delay();
+    self.sda_is_high()
+  }
+
+  fn set_sda(&mut self, high: bool) {
+    self.sda = self.sda.take().map(|line| {
+      if high {
+        line.release()
+      } else {
+        line.pull_low()
+      }
+    });
+  }
+
+  fn scl_low(&mut self) {
+    self.scl = self.scl.take().map(Line::pull_low);
+  }
+
+  fn scl_release(&mut self) {
+    self.scl = self.scl.take().map(Line::release);
+  }
+
+  /// Release SCL and wait for the slave to stop stretching the clock
+  fn scl_high(&mut self) -> Result<(), BusError> {
+    self.scl_release();
+    let mut waited = 0;
+    while !self.scl.as_ref().map(Line::is_high).unwrap_or(true) {
+      // the clock stretch timeout counts SCL clock cycles
+      if self.stretch_limit != 0 && waited >= self.stretch_limit * 2 {
+        return Err(BusError::ClockStretchTimeout);
+      }
+      self.delay();
+      waited += 1;
+    }
+    Ok(())
+  }
+
+  fn sda_is_high(&self) -> bool {
+    self.sda.as_ref().map(Line::is_high).unwrap_or(true)
+  }
+
+  fn delay(&self) {
+    timer::sleep(self.half_period);
+  }
+
+  /// Issue a START condition, or a repeated START if the bus is still owned by the controller
+  fn start_condition(&mut self, repeated: bool) -> Result<(), BusError> {
+    if repeated {
+      self.set_sda(true);
+      self.delay();
+      self.scl_high()?;
+      self.delay();
+    }
+    self.set_sda(false);
+    self.delay();
+    self.scl_low();
+    Ok(())
+  }
+
+  /// Issue a STOP condition releasing the bus
+  fn stop_condition(&mut self) -> Result<(), BusError> {
+    self.set_sda(false);
+    self.delay();
+    let released = self.scl_high();
+    self.delay();
+    self.set_sda(true);
+    self.delay();
+    released
+  }
+
+  fn write_bit(&mut self, bit: bool) -> Result<(), BusError> {
+    self.set_sda(bit);
+    self.delay();
+    self.scl_high()?;
+    self.delay();
+    self.scl_low();
+    Ok(())
+  }
+
+  fn read_bit(&mut self) -> Result<bool, BusError> {
+    self.set_sda(true);
+    self.delay();
+    self.scl_high()?;
+    let bit = self.sda_is_high();
+    self.delay();
+    self.scl_low();
+    Ok(bit)
+  }
+
+  /// Write a byte to the bus and return whether the slave has acknowledged it
+  fn write_byte(&mut self, data: u8) -> Result<bool, BusError> {
+    for bit in (0..8).rev() {
+      self.write_bit(data & (1 << bit) != 0)?;
+    }
+    Ok(!self.read_bit()?)
+  }
+
+  /// Read a byte from the bus and acknowledge it if further bytes shall be read
+  fn read_byte(&mut self, ack: bool) -> Result<u8, BusError> {
+    let mut data = 0;
+    for _ in 0..8 {
+      data = (data << 1) | self.read_bit()? as u8;
+    }
+    self.write_bit(!ack)?;
+    Ok(data)
+  }
+}
+
+impl SoftBsc {
+  /// Create a software controller on the given GPIO pins. The `core_speed` is the clock the clock divisor register
+  /// relates to and shall match the core speed the bus is initialized with. Both pins are reserved for exclusive use
+  /// by the controller until it is dropped.
+  pub fn new(sda: u32, scl: u32, core_speed: u32) -> I2cResult<Self> {
+    let (sda_line, scl_line) = GPIO.with_mut(|gpio| {
+      let sda_line = gpio
+        .get_pin(sda)
+        .map_err(|_| I2cError::PinUnavailable { pin: sda })?
+        .into_pud_up()
+        .into_input();
+      match gpio.get_pin(scl) {
+        Ok(pin) => Ok((sda_line, pin.into_pud_up().into_input())),
+        Err(_) => {
+          // release the first pin again as the bus could not be used without the second one
+          gpio.free_pin(sda);
+          Err(I2cError::PinUnavailable { pin: scl })
+        }
+      }
+    })?;
+
+    Ok(SoftBsc {
+      sda,
+      scl,
+      state: RefCell::new(BscModel::new(Wire {
+        sda: Some(Line::Released(sda_line)),
+        scl: Some(Line::Released(scl_line)),
+        core_speed,
+        half_period: Duration::from_micros(0),
+        stretch_limit: 0,
+      })),
+    })
+  }
+
+  /// The GPIO pin carrying the data signal
+  pub fn sda(&self) -> u32 {
+    self.sda
+  }
+
+  /// The GPIO pin carrying the clock signal
+  pub fn scl(&self) -> u32 {
+    self.scl
+  }
+}
+
+impl Drop for SoftBsc {
+  fn drop(&mut self) {
+    GPIO.with_mut(|gpio| {
+      gpio.free_pin(self.sda);
+      gpio.free_pin(self.scl);
+    });
+  }
+}
+
+impl BscBackend for SoftBsc {
+  fn read(&self, reg: BscRegister) -> u32 {
+    self.state.borrow_mut().read(reg)
+  }
+
+  fn write(&self, reg: BscRegister, value: u32) {
+    self.state.borrow_mut().write(reg, value);
+  }
+
+  fn recover_bus(&self) -> I2cResult<()> {
+    if self.state.borrow_mut().bus.recover() {
+      Ok(())
+    } else {
+      Err(I2cError::BusStuck)
+    }
+  }
+}
+
+impl Bus for Wire {
+  fn configure(&mut self, divisor: u32, clkt: u32) {
+    // each half of the SCL clock period lasts half of the divisor in core clock cycles
+    self.half_period =
+      Duration::from_nanos(divisor as u64 * 500_000_000 / self.core_speed.max(1) as u64);
+    self.stretch_limit = clkt;
+  }
+
+  fn start(&mut self, addr: u8, read: bool, repeated: bool) -> Result<(), BusError> {
+    self.start_condition(repeated)?;
+    ack(self.write_byte((addr << 1) | read as u8)?)
+  }
+
+  fn write(&mut self, data: u8) -> Result<(), BusError> {
+    ack(self.write_byte(data)?)
+  }
+
+  fn read(&mut self, ack: bool) -> Result<u8, BusError> {
+    self.read_byte(ack)
+  }
+
+  fn stop(&mut self) -> Result<(), BusError> {
+    self.stop_condition()
+  }
+}
+
+/// A byte not acknowledged by the slave fails the transfer
+fn ack(acknowledged: bool) -> Result<(), BusError> {
+  if acknowledged {
+    Ok(())
+  } else {
+    Err(BusError::Nack)
+  }
+}
@@ -0,0 +1,242 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # I²C bus configuration
+//!
+//! The configuration the I²C bus is initialized with. The bus frequency is derived from the core clock of the
+//! Raspberry Pi with the clock divisor of the BSC controller. The clock stretch timeout and the data delays are
+//! checked against this divisor.
+//!
+
+use crate::{I2cError, I2cResult};
+
+/// The I²C standard mode frequency of 100 kHz
+pub const STANDARD_MODE: u32 = 100_000;
+/// The I²C fast mode frequency of 400 kHz
+pub const FAST_MODE: u32 = 400_000;
+/// The I²C fast mode plus frequency of 1 MHz, the highest bus frequency supported by the BSC controller
+pub const FAST_MODE_PLUS: u32 = 1_000_000;
+
+const CDIV_MAX: u32 = 0xFFFE; // the biggest even clock divisor fitting into the CDIV register
+const CLKT_MAX: u32 = 0xFFFF; // the biggest clock stretch timeout fitting into the CLKT register
+const CLKT_DEFAULT: u16 = 0x40; // the reset value of the clock stretch timeout
+const DEL_DEFAULT: u16 = 0x30; // the reset value of the falling and rising edge delay
+
+/// The time a device is allowed to stretch the clock by holding the SCL line low
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockStretchTimeout {
+  /// A device may stretch the clock for an unlimited time
+  Disabled,
+  /// The timeout in SCL clock cycles of the bus
+  Cycles(u16),
+  /// The timeout in microseconds, converted into SCL clock cycles of the actual bus frequency
+  Micros(u32),
+}
+
+/// The delay of sampling (falling edge) and changing (rising edge) the SDA line after an edge of the SCL line in core
+/// clock cycles. Each delay need to be less than half of the clock divisor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataDelay {
+  /// The delay after the falling edge of SCL before the next data bit is output (FEDL)
+  pub falling_edge: u16,
+  /// The delay after the rising edge of SCL before the data bit is sampled (REDL)
+  pub rising_edge: u16,
+}
+
+impl DataDelay {
+  /// Create the data delay with the given delays in core clock cycles
+  pub const fn new(falling_edge: u16, rising_edge: u16) -> Self {
+    DataDelay {
+      falling_edge,
+      rising_edge,
+    }
+  }
+}
+
+impl Default for DataDelay {
+  fn default() -> Self {
+    DataDelay::new(DEL_DEFAULT, DEL_DEFAULT)
+  }
+}
+
+/// The configuration of the I²C bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct I2cConfig {
+  /// The core clock speed in Hz the BSC controller is driven with
+  pub core_speed: u32,
+  /// The requested frequency of the I²C bus clock in Hz
+  pub frequency: u32,
+  /// The time a device is allowed to stretch the clock
+  pub clock_stretch_timeout: ClockStretchTimeout,
+  /// The delays of the SDA line after the edges of the SCL line
+  pub data_delay: DataDelay,
+}
+
+impl I2cConfig {
+  /// Create the configuration for the given core clock speed and the requested bus frequency. The clock stretch
+  /// timeout and the data delays are set to the reset values of the controller.
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # fn doc() {
+  ///     // run the bus at 20 kHz for a long cable
+  ///     let config = I2cConfig::new(250_000_000, 20_000);
+  ///     let frequency = I2C.with_mut(|i2c| i2c.initialize_with_config(config)).unwrap();
+  /// # }
+  /// ```
+  pub const fn new(core_speed: u32, frequency: u32) -> Self {
+    I2cConfig {
+      core_speed,
+      frequency,
+      clock_stretch_timeout: ClockStretchTimeout::Cycles(CLKT_DEFAULT),
+      data_delay: DataDelay::new(DEL_DEFAULT, DEL_DEFAULT),
+    }
+  }
+
+  /// Use the given clock stretch timeout with this configuration
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # fn doc() {
+  ///     // allow a slow sensor to stretch the clock for up to 2ms
+  ///     let config = I2cConfig::new(250_000_000, STANDARD_MODE)
+  ///         .with_clock_stretch_timeout(ClockStretchTimeout::Micros(2_000));
+  ///     I2C.with_mut(|i2c| i2c.initialize_with_config(config)).unwrap();
+  /// # }
+  /// ```
+  pub const fn with_clock_stretch_timeout(
+    self,
+    clock_stretch_timeout: ClockStretchTimeout,
+  ) -> Self {
+    I2cConfig {
+      clock_stretch_timeout,
+      ..self
+    }
+  }
+
+  /// Use the given data delays with this configuration
+  pub const fn with_data_delay(self, data_delay: DataDelay) -> Self {
+    I2cConfig { data_delay, ..self }
+  }
+
+  /// The clock divisor of the BSC controller for this configuration. The controller only uses even divisors, so the
+  /// divisor is rounded up to the next even value. The resulting bus frequency never exceeds the requested one.
+  /// If the requested frequency could not be achieved with the core clock speed or exceeds the [FAST_MODE_PLUS]
+  /// the controller supports at most an error is returned.
+  /// # Example
+  /// ```
+  /// # use ruspiro_i2c::*;
+  /// // 250 MHz / 400 kHz = 625 is rounded up to the even divisor 626
+  /// let config = I2cConfig::new(250_000_000, FAST_MODE);
+  /// assert_eq!(config.clock_divisor(), Ok(626));
+  /// assert_eq!(config.actual_frequency(), Ok(399_361));
+  ///
+  /// assert_eq!(I2cConfig::new(250_000_000, 10_000).clock_divisor(), Ok(25_000));
+  /// assert_eq!(I2cConfig::new(250_000_000, FAST_MODE_PLUS).clock_divisor(), Ok(250));
+  ///
+  /// // neither a missing core clock nor a divisor exceeding the CDIV register could be used
+  /// let invalid = Err(I2cError::InvalidFrequency { frequency: 3_000 });
+  /// assert_eq!(I2cConfig::new(250_000_000, 3_000).clock_divisor(), invalid);
+  /// assert_eq!(I2cConfig::new(0, 3_000).clock_divisor(), invalid);
+  /// assert_eq!(I2cConfig::new(0, 3_000).actual_frequency(), invalid);
+  /// let timeout = I2cConfig::new(0, 3_000).with_clock_stretch_timeout(ClockStretchTimeout::Micros(100));
+  /// assert_eq!(timeout.timeout_cycles(), invalid);
+  /// assert_eq!(
+  ///     I2cConfig::new(250_000_000, 0).clock_divisor(),
+  ///     Err(I2cError::InvalidFrequency { frequency: 0 })
+  /// );
+  ///
+  /// // the controller is not able to drive the bus faster than Fast-mode Plus
+  /// assert_eq!(
+  ///     I2cConfig::new(250_000_000, FAST_MODE_PLUS + 1).clock_divisor(),
+  ///     Err(I2cError::InvalidFrequency { frequency: 1_000_001 })
+  /// );
+  /// assert_eq!(
+  ///     I2cConfig::new(250_000_000, 250_000_000).clock_divisor(),
+  ///     Err(I2cError::InvalidFrequency { frequency: 250_000_000 })
+  /// );
+  /// ```
+  pub fn clock_divisor(&self) -> I2cResult<u32> {
+    if self.frequency == 0 || self.frequency > FAST_MODE_PLUS {
+      return Err(I2cError::InvalidFrequency {
+        frequency: self.frequency,
+      });
+    }
+    let divisor = (self.core_speed as u64).div_ceil(self.frequency as u64);
+    let divisor = (divisor + 1) & !1;
+    // a core clock speed of 0 results in a divisor of 0, which the controller would take as 32768
+    if divisor == 0 || divisor > CDIV_MAX as u64 {
+      return Err(I2cError::InvalidFrequency {
+        frequency: self.frequency,
+      });
+    }
+    Ok(divisor as u32)
+  }
+
+  /// The bus frequency actually achieved with this configuration
+  pub fn actual_frequency(&self) -> I2cResult<u32> {
+    Ok(self.core_speed / self.clock_divisor()?)
+  }
+
+  /// The clock stretch timeout in SCL clock cycles of the actual bus frequency. A value of 0 disables the timeout.
+  /// A timeout exceeding the 65535 cycles the controller is able to count is rejected.
+  /// # Example
+  /// ```
+  /// # use ruspiro_i2c::*;
+  /// // the bus runs at exactly 100 kHz, so each clock cycle takes 10µs
+  /// let config = I2cConfig::new(250_000_000, STANDARD_MODE);
+  /// let cycles = |timeout| config.with_clock_stretch_timeout(timeout).timeout_cycles();
+  /// assert_eq!(cycles(ClockStretchTimeout::Disabled), Ok(0));
+  /// assert_eq!(cycles(ClockStretchTimeout::Cycles(0xFFFF)), Ok(0xFFFF));
+  /// assert_eq!(cycles(ClockStretchTimeout::Micros(1)), Ok(1));
+  /// assert_eq!(cycles(ClockStretchTimeout::Micros(655_350)), Ok(0xFFFF));
+  /// assert_eq!(
+  ///     cycles(ClockStretchTimeout::Micros(655_351)),
+  ///     Err(I2cError::InvalidClockStretchTimeout)
+  /// );
+  /// assert_eq!(
+  ///     cycles(ClockStretchTimeout::Micros(0)),
+  ///     Err(I2cError::InvalidClockStretchTimeout)
+  /// );
+  /// ```
+  pub fn timeout_cycles(&self) -> I2cResult<u32> {
+    let cycles = match self.clock_stretch_timeout {
+      ClockStretchTimeout::Disabled => 0,
+      ClockStretchTimeout::Cycles(cycles) => cycles as u64,
+      ClockStretchTimeout::Micros(0) => return Err(I2cError::InvalidClockStretchTimeout),
+      ClockStretchTimeout::Micros(micros) => {
+        (micros as u64 * self.actual_frequency()? as u64).div_ceil(1_000_000)
+      }
+    };
+    if cycles > CLKT_MAX as u64 {
+      return Err(I2cError::InvalidClockStretchTimeout);
+    }
+    Ok(cycles as u32)
+  }
+
+  /// The value of the data delay register. Each delay need to be less than half of the clock divisor, otherwise the
+  /// controller would sample or change the SDA line after the next edge of SCL.
+  /// # Example
+  /// ```
+  /// # use ruspiro_i2c::*;
+  /// // the clock divisor of 2500 allows delays up to 1249 core clock cycles
+  /// let config = I2cConfig::new(250_000_000, STANDARD_MODE);
+  /// let value = |falling, rising| config.with_data_delay(DataDelay::new(falling, rising)).data_delay_value();
+  /// assert_eq!(value(0, 0), Ok(0));
+  /// assert_eq!(value(1249, 1249), Ok(1249 << 16 | 1249));
+  /// assert_eq!(value(1250, 0), Err(I2cError::InvalidDataDelay));
+  /// assert_eq!(value(0, 1250), Err(I2cError::InvalidDataDelay));
+  /// ```
+  pub fn data_delay_value(&self) -> I2cResult<u32> {
+    let limit = self.clock_divisor()? / 2;
+    let delay = self.data_delay;
+    if delay.falling_edge as u32 >= limit || delay.rising_edge as u32 >= limit {
+      return Err(I2cError::InvalidDataDelay);
+    }
+    Ok((delay.falling_edge as u32) << 16 | delay.rising_edge as u32)
+  }
+}
@@ -0,0 +1,101 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # BSC controllers
+//!
+//! The Raspberry Pi provides several Broadcom Serial Controllers (BSC) each able to drive an I²C bus. The BCM2837 of
+//! the Raspberry Pi 3 provides BSC0 and BSC1, the BCM2711 of the Raspberry Pi 4 additionally provides BSC3 to BSC6.
+//! BSC2 is reserved for the HDMI interface and therefore not available.
+//!
+
+#[cfg(all(feature = "ruspiro_pi3", feature = "ruspiro_pi4"))]
+compile_error!("the features ruspiro_pi3 and ruspiro_pi4 are exclusive, use --no-default-features with ruspiro_pi4");
+
+#[cfg(all(feature = "ruspiro_pi3", not(feature = "ruspiro_pi4")))]
+const PERIPHERAL_BASE: usize = 0x3F00_0000;
+
+#[cfg(feature = "ruspiro_pi4")]
+const PERIPHERAL_BASE: usize = 0xFE00_0000;
+
+/// The BSC controllers usable as I²C bus master
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bsc {
+  /// BSC0 connected to the GPIO pins 0 and 1. On the Raspberry Pi those pins are reserved for the HAT ID EEPROM.
+  Bsc0,
+  /// BSC1 connected to the GPIO pins 2 and 3. This is the I²C bus available at the pin header by default.
+  Bsc1,
+  /// BSC3 connected to the GPIO pins 4 and 5
+  #[cfg(feature = "ruspiro_pi4")]
+  Bsc3,
+  /// BSC4 connected to the GPIO pins 6 and 7
+  #[cfg(feature = "ruspiro_pi4")]
+  Bsc4,
+  /// BSC5 connected to the GPIO pins 10 and 11
+  #[cfg(feature = "ruspiro_pi4")]
+  Bsc5,
+  /// BSC6 connected to the GPIO pins 22 and 23
+  #[cfg(feature = "ruspiro_pi4")]
+  Bsc6,
+}
+
+/// The alternative GPIO pin function that connects a pin to the BSC controller
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AltFunction {
+  Alt0,
+  Alt5,
+}
+
+/// The GPIO pins a BSC controller is connected to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BscPins {
+  /// the GPIO pin carrying the data signal
+  pub sda: u32,
+  /// the GPIO pin carrying the clock signal
+  pub scl: u32,
+  /// the alternative function the pins need to be configured to
+  pub alt_function: AltFunction,
+}
+
+impl Bsc {
+  /// The MMIO base address of the controller registers
+  pub const fn base(self) -> usize {
+    PERIPHERAL_BASE
+      + match self {
+        Bsc::Bsc0 => 0x0020_5000,
+        Bsc::Bsc1 => 0x0080_4000,
+        #[cfg(feature = "ruspiro_pi4")]
+        Bsc::Bsc3 => 0x0020_5600,
+        #[cfg(feature = "ruspiro_pi4")]
+        Bsc::Bsc4 => 0x0020_5800,
+        #[cfg(feature = "ruspiro_pi4")]
+        Bsc::Bsc5 => 0x0020_5A80,
+        #[cfg(feature = "ruspiro_pi4")]
+        Bsc::Bsc6 => 0x0020_5C00,
+      }
+  }
+
+  /// The GPIO pins the controller is connected to
+  pub const fn pins(self) -> BscPins {
+    let (sda, scl, alt_function) = match self {
+      Bsc::Bsc0 => (0, 1, AltFunction::Alt0),
+      Bsc::Bsc1 => (2, 3, AltFunction::Alt0),
+      #[cfg(feature = "ruspiro_pi4")]
+      Bsc::Bsc3 => (4, 5, AltFunction::Alt5),
+      #[cfg(feature = "ruspiro_pi4")]
+      Bsc::Bsc4 => (6, 7, AltFunction::Alt5),
+      #[cfg(feature = "ruspiro_pi4")]
+      Bsc::Bsc5 => (10, 11, AltFunction::Alt5),
+      #[cfg(feature = "ruspiro_pi4")]
+      Bsc::Bsc6 => (22, 23, AltFunction::Alt5),
+    };
+    BscPins {
+      sda,
+      scl,
+      alt_function,
+    }
+  }
+}
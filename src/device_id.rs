@@ -0,0 +1,99 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Device ID
+//!
+//! Devices supporting the Device ID of the I²C specification (UM10204) identify themselves with three bytes read from
+//! the reserved address ``1111 100``. They carry the 12 bit manufacturer code assigned by NXP, the 9 bit part
+//! identification assigned by the manufacturer and the 3 bit die revision.
+//!
+//! # Example
+//! ```
+//! # use ruspiro_i2c::*;
+//! let id = DeviceId::from([0x00, 0x50, 0x0B]);
+//! assert_eq!(id.manufacturer(), 0x005);
+//! assert_eq!(id.manufacturer_name(), Some("Analog Devices"));
+//! assert_eq!(id.part(), 0x001);
+//! assert_eq!(id.revision(), 3);
+//! ```
+//!
+
+use core::fmt;
+
+/// The manufacturer codes of the Device ID assigned by NXP as listed in the I²C specification
+pub const MANUFACTURERS: &[(u16, &str)] = &[
+  (0x000, "NXP Semiconductors"),
+  (0x001, "NXP Semiconductors (reserved)"),
+  (0x002, "NXP Semiconductors (reserved)"),
+  (0x003, "NXP Semiconductors (reserved)"),
+  (0x004, "Ramtron International"),
+  (0x005, "Analog Devices"),
+  (0x006, "STMicroelectronics"),
+  (0x007, "ON Semiconductor"),
+  (0x008, "Sprintek Corporation"),
+  (0x009, "ESPROS Photonics AG"),
+  (0x00A, "Fujitsu Semiconductor"),
+  (0x00B, "Flir"),
+  (0x00C, "O2Micro"),
+  (0x00D, "Atmel"),
+];
+
+/// The Device ID read from a device with [I2cImpl::read_device_id](crate::I2cImpl::read_device_id)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeviceId([u8; 3]);
+
+impl DeviceId {
+  /// The 12 bit manufacturer code
+  pub fn manufacturer(self) -> u16 {
+    (self.0[0] as u16) << 4 | (self.0[1] >> 4) as u16
+  }
+
+  /// The name of the manufacturer, if its code is contained in [MANUFACTURERS]
+  pub fn manufacturer_name(self) -> Option<&'static str> {
+    let code = self.manufacturer();
+    MANUFACTURERS
+      .iter()
+      .find(|(manufacturer, _)| *manufacturer == code)
+      .map(|(_, name)| *name)
+  }
+
+  /// The 9 bit part identification assigned by the manufacturer
+  pub fn part(self) -> u16 {
+    ((self.0[1] & 0x0F) as u16) << 5 | (self.0[2] >> 3) as u16
+  }
+
+  /// The 3 bit die revision
+  pub fn revision(self) -> u8 {
+    self.0[2] & 0x07
+  }
+
+  /// The three raw bytes of the Device ID
+  pub fn bytes(self) -> [u8; 3] {
+    self.0
+  }
+}
+
+impl From<[u8; 3]> for DeviceId {
+  fn from(bytes: [u8; 3]) -> Self {
+    DeviceId(bytes)
+  }
+}
+
+impl fmt::Display for DeviceId {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.manufacturer_name() {
+      Some(name) => write!(f, "{}", name)?,
+      None => write!(f, "manufacturer 0x{:03X}", self.manufacturer())?,
+    }
+    write!(
+      f,
+      " part 0x{:03X} revision {}",
+      self.part(),
+      self.revision()
+    )
+  }
+}
@@ -0,0 +1,114 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # I²C errors
+//!
+//! The different failures an I²C bus operation may end up with. Wherever possible the error carries the slave address
+//! the failing transfer was targeting, so a driver is able to decide whether retrying the operation makes sense.
+//!
+
+use core::fmt;
+
+use crate::Address;
+
+/// The error returned by all I²C bus operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I2cError {
+  /// The slave did not acknowledge its address, so most likely there is no device connected at this address
+  AddressNack { addr: Address },
+  /// The slave acknowledged its address but did not acknowledge a data byte. The `index` is the position of the
+  /// byte within the transfer that was not acknowledged (the register id of a register write counts as byte 0)
+  DataNack { addr: Address, index: usize },
+  /// The slave held the SCL line low longer than the clock stretch timeout allows
+  ClockStretchTimeout { addr: Address },
+  /// The transfer has not been finished by the I²C peripheral within the expected time
+  Timeout { addr: Address },
+  /// The I²C bus has not been initialized yet
+  NotInitialized,
+  /// The GPIO pin required for the I²C bus is already in use elsewhere
+  PinUnavailable { pin: u32 },
+  /// The requested transfer length can not be handled by the I²C peripheral
+  InvalidLength { len: usize },
+  /// An interrupt driven transfer is still in progress on the I²C bus
+  Busy,
+  /// The requested bus frequency could not be achieved with the clock divisor of the BSC controller
+  InvalidFrequency { frequency: u32 },
+  /// The clock stretch timeout does not fit into the timeout register of the BSC controller
+  InvalidClockStretchTimeout,
+  /// The data delay is not less than half of the clock divisor of the BSC controller
+  InvalidDataDelay,
+  /// A device still holds the SDA line low after the bus recovery
+  BusStuck,
+  /// The backend does not drive the bus lines and is therefore not able to recover the bus
+  RecoveryUnsupported,
+  /// The transaction contains a write following a read, which the BSC controller is not able to join with a
+  /// repeated START condition
+  UnsupportedTransaction,
+  /// The slave address exceeds the range of its address type
+  InvalidAddress { addr: Address },
+  /// The SMBus Packet Error Code received from the device does not match the data received
+  PecMismatch { addr: Address },
+  /// The PMBus ``VOUT_MODE`` of the device selects a data format not supported by the requested conversion
+  UnsupportedVoutMode { mode: u8 },
+}
+
+impl I2cError {
+  /// The slave address of the device the failed transfer was targeting, if there is any
+  pub fn addr(&self) -> Option<Address> {
+    match *self {
+      I2cError::AddressNack { addr }
+      | I2cError::DataNack { addr, .. }
+      | I2cError::ClockStretchTimeout { addr }
+      | I2cError::Timeout { addr }
+      | I2cError::PecMismatch { addr } => Some(addr),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for I2cError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match *self {
+      I2cError::AddressNack { addr } => write!(f, "I2C address {} not acknowledged", addr),
+      I2cError::DataNack { addr, index } => write!(
+        f,
+        "I2C transmit of byte {} not acknowledged by device {}",
+        index, addr
+      ),
+      I2cError::ClockStretchTimeout { addr } => {
+        write!(f, "I2C device {} exceeded the clock stretch timeout", addr)
+      }
+      I2cError::Timeout { addr } => {
+        write!(f, "time out waiting for I2C transmit to {}", addr)
+      }
+      I2cError::NotInitialized => write!(f, "I2C Bus not initialized"),
+      I2cError::PinUnavailable { pin } => write!(f, "GPIO pin {} not available for I2C", pin),
+      I2cError::InvalidLength { len } => write!(f, "invalid I2C transfer length {}", len),
+      I2cError::Busy => write!(f, "I2C bus busy with an interrupt driven transfer"),
+      I2cError::InvalidFrequency { frequency } => {
+        write!(f, "I2C bus frequency of {} Hz not supported", frequency)
+      }
+      I2cError::InvalidClockStretchTimeout => write!(f, "I2C clock stretch timeout out of range"),
+      I2cError::InvalidDataDelay => write!(f, "I2C data delay exceeds half of the clock period"),
+      I2cError::BusStuck => write!(f, "I2C SDA line still held low after bus recovery"),
+      I2cError::RecoveryUnsupported => write!(f, "I2C bus recovery not supported by the backend"),
+      I2cError::UnsupportedTransaction => {
+        write!(
+          f,
+          "I2C transaction with a write following a read not supported"
+        )
+      }
+      I2cError::InvalidAddress { addr } => write!(f, "invalid I2C address {}", addr),
+      I2cError::PecMismatch { addr } => {
+        write!(f, "SMBus packet error code mismatch from device {}", addr)
+      }
+      I2cError::UnsupportedVoutMode { mode } => {
+        write!(f, "PMBus VOUT_MODE 0x{:02X} not supported", mode)
+      }
+    }
+  }
+}
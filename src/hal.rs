@@ -0,0 +1,172 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # embedded-hal support
+//!
+//! Implementation of the ``embedded-hal`` 1.0 and ``embedded-hal-async`` I²C traits, so device drivers written
+//! against those traits could be used with this I²C bus.
+//!
+//! The BSC controller is able to continue a write with a read by a repeated START condition only. Consecutive
+//! operations of the same kind are merged, so a transaction is made of writes followed by reads. A transaction with a
+//! write following a read fails with [I2cError::UnsupportedTransaction] without any transfer on the bus, instead of
+//! separating the operations by a STOP condition the device might lose its state with:
+//! ```
+//! # use ruspiro_i2c::{I2cError, I2cImpl};
+//! # use ruspiro_i2c::backend::sim::*;
+//! use embedded_hal::i2c::{I2c, Operation};
+//!
+//! let device = SimRegisterDevice::new(256);
+//! device.set_register(0x10, 0x01);
+//! device.set_register(0x11, 0x02);
+//! let recorder = SimRecorder::new(device.clone());
+//! let bsc = SimulatedBsc::new();
+//! bsc.attach(0x68, recorder.clone());
+//! let mut i2c = I2cImpl::with_backend(bsc);
+//! i2c.initialize(250_000_000, true).unwrap();
+//!
+//! let mut first = [0; 1];
+//! let mut second = [0; 1];
+//! i2c.transaction(
+//!     0x68,
+//!     &mut [
+//!         Operation::Write(&[]),
+//!         Operation::Write(&[0x10]),
+//!         Operation::Read(&mut first),
+//!         Operation::Read(&mut second),
+//!     ],
+//! )
+//! .unwrap();
+//! assert_eq!((first, second), ([0x01], [0x02]));
+//! assert_eq!(
+//!     recorder.events(),
+//!     [
+//!         SimEvent::Start { read: false },
+//!         SimEvent::Write(0x10),
+//!         SimEvent::Start { read: true },
+//!         SimEvent::Read(0x01),
+//!         SimEvent::Read(0x02),
+//!         SimEvent::Stop,
+//!     ]
+//! );
+//!
+//! recorder.clear();
+//! assert_eq!(
+//!     i2c.transaction(
+//!         0x68,
+//!         &mut [
+//!             Operation::Write(&[0x10]),
+//!             Operation::Read(&mut first),
+//!             Operation::Write(&[0x20, 0x05]),
+//!         ],
+//!     ),
+//!     Err(I2cError::UnsupportedTransaction)
+//! );
+//! assert!(recorder.events().is_empty());
+//!
+//! // the asynchronous transaction processes the operations in the same way
+//! # fn block_on<F: core::future::Future>(future: F) -> F::Output {
+//! #     use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+//! #     fn raw() -> RawWaker {
+//! #         RawWaker::new(core::ptr::null(), &VTABLE)
+//! #     }
+//! #     static VTABLE: RawWakerVTable = RawWakerVTable::new(|_| raw(), |_| (), |_| (), |_| ());
+//! #     let waker = unsafe { Waker::from_raw(raw()) };
+//! #     let mut future = Box::pin(future);
+//! #     loop {
+//! #         if let Poll::Ready(output) = future.as_mut().poll(&mut Context::from_waker(&waker)) {
+//! #             break output;
+//! #         }
+//! #     }
+//! # }
+//! block_on(embedded_hal_async::i2c::I2c::transaction(
+//!     &mut i2c,
+//!     0x68,
+//!     &mut [Operation::Write(&[0x11]), Operation::Read(&mut first)],
+//! ))
+//! .unwrap();
+//! assert_eq!(first, [0x02]);
+//! assert_eq!(
+//!     recorder.events(),
+//!     [
+//!         SimEvent::Start { read: false },
+//!         SimEvent::Write(0x11),
+//!         SimEvent::Start { read: true },
+//!         SimEvent::Read(0x02),
+//!         SimEvent::Stop,
+//!     ]
+//! );
+//!
+//! recorder.clear();
+//! assert_eq!(
+//!     block_on(embedded_hal_async::i2c::I2c::transaction(
+//!         &mut i2c,
+//!         0x68,
+//!         &mut [Operation::Read(&mut first), Operation::Write(&[0x30])],
+//!     )),
+//!     Err(I2cError::UnsupportedTransaction)
+//! );
+//! assert!(recorder.events().is_empty());
+//! ```
+//!
+
+use embedded_hal::i2c::{
+  self, ErrorKind, ErrorType, NoAcknowledgeSource, Operation, SevenBitAddress,
+};
+
+use crate::backend::BscBackend;
+use crate::{interface, Address, I2cError, I2cImpl};
+
+impl i2c::Error for I2cError {
+  fn kind(&self) -> ErrorKind {
+    match *self {
+      I2cError::AddressNack { .. } => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address),
+      I2cError::DataNack { .. } => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data),
+      // the device holding the clock line low is a bus error
+      I2cError::ClockStretchTimeout { .. } => ErrorKind::Bus,
+      // the BSC controller is the only master on the bus and does not detect an arbitration loss, everything else
+      // is not covered by the embedded-hal error kinds
+      _ => ErrorKind::Other,
+    }
+  }
+}
+
+impl<B: BscBackend> ErrorType for I2cImpl<B> {
+  type Error = I2cError;
+}
+
+/// The operations of a transaction are limited to writes followed by reads, as the BSC controller joins a read
+/// following a write with a repeated START condition only. A write following a read fails with
+/// [I2cError::UnsupportedTransaction].
+impl<B: BscBackend> i2c::I2c<SevenBitAddress> for I2cImpl<B> {
+  fn transaction(
+    &mut self,
+    address: SevenBitAddress,
+    operations: &mut [Operation<'_>],
+  ) -> Result<(), Self::Error> {
+    self.is_initializied()?;
+    interface::transaction(
+      &self.backend,
+      &self.timing(),
+      Address::SevenBit(address),
+      operations,
+    )
+  }
+}
+
+/// The operations of a transaction are limited to writes followed by reads in the same way as for the blocking
+/// transaction.
+impl<B: BscBackend> embedded_hal_async::i2c::I2c<SevenBitAddress> for I2cImpl<B> {
+  async fn transaction(
+    &mut self,
+    address: SevenBitAddress,
+    operations: &mut [Operation<'_>],
+  ) -> Result<(), Self::Error> {
+    self
+      .transaction_async(Address::SevenBit(address), operations)
+      .await
+  }
+}
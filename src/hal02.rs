@@ -0,0 +1,114 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # embedded-hal 0.2 support
+//!
+//! Implementation of the ``embedded-hal`` 0.2 blocking I²C traits for a shared reference to the I²C bus. As the
+//! reference is a copyable handle onto the bus, several device drivers written against those traits are able to use
+//! the same bus side by side with drivers using the ``embedded-hal`` 1.0 traits.
+//!
+//! A ``write_read`` joins the write and the read with a repeated START condition and ``WriteIter`` collects the
+//! bytes to know the length of the transfer before it is started:
+//! ```
+//! # use ruspiro_i2c::I2cImpl;
+//! # use ruspiro_i2c::backend::sim::*;
+//! use embedded_hal_02::blocking::i2c::{WriteIter, WriteRead};
+//!
+//! let device = SimRegisterDevice::new(256);
+//! device.set_register(0x10, 0x01);
+//! device.set_register(0x11, 0x02);
+//! let recorder = SimRecorder::new(device.clone());
+//! let bsc = SimulatedBsc::new();
+//! bsc.attach(0x68, recorder.clone());
+//! let mut i2c = I2cImpl::with_backend(bsc);
+//! i2c.initialize(250_000_000, true).unwrap();
+//!
+//! let mut buffer = [0; 2];
+//! (&i2c).write_read(0x68, &[0x10], &mut buffer).unwrap();
+//! assert_eq!(buffer, [0x01, 0x02]);
+//! assert_eq!(
+//!     recorder.events(),
+//!     [
+//!         SimEvent::Start { read: false },
+//!         SimEvent::Write(0x10),
+//!         SimEvent::Start { read: true },
+//!         SimEvent::Read(0x01),
+//!         SimEvent::Read(0x02),
+//!         SimEvent::Stop,
+//!     ]
+//! );
+//!
+//! recorder.clear();
+//! WriteIter::write(&mut &i2c, 0x68, (0x20..0x23).map(|value| value as u8)).unwrap();
+//! // the first byte selects the register the following bytes are written to
+//! assert_eq!((device.register(0x20), device.register(0x21)), (0x21, 0x22));
+//! assert_eq!(
+//!     recorder.events(),
+//!     [
+//!         SimEvent::Start { read: false },
+//!         SimEvent::Write(0x20),
+//!         SimEvent::Write(0x21),
+//!         SimEvent::Write(0x22),
+//!         SimEvent::Stop,
+//!     ]
+//! );
+//! ```
+//!
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use embedded_hal_02::blocking::i2c::{Read, Write, WriteIter, WriteRead};
+
+use crate::backend::BscBackend;
+use crate::{interface, I2cError, I2cImpl};
+
+impl<B: BscBackend> Write for &I2cImpl<B> {
+  type Error = I2cError;
+
+  fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+    self.is_initializied()?;
+    interface::write_data(&self.backend, &self.timing(), address.into(), bytes)
+  }
+}
+
+impl<B: BscBackend> WriteIter for &I2cImpl<B> {
+  type Error = I2cError;
+
+  fn write<I>(&mut self, address: u8, bytes: I) -> Result<(), Self::Error>
+  where
+    I: IntoIterator<Item = u8>,
+  {
+    self.is_initializied()?;
+    // the data length need to be known upfront to start the transfer
+    let bytes: Vec<u8> = bytes.into_iter().collect();
+    interface::write_data(&self.backend, &self.timing(), address.into(), &bytes)
+  }
+}
+
+impl<B: BscBackend> Read for &I2cImpl<B> {
+  type Error = I2cError;
+
+  fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+    self.is_initializied()?;
+    interface::read_data(&self.backend, &self.timing(), address.into(), buffer).map(|_| ())
+  }
+}
+
+impl<B: BscBackend> WriteRead for &I2cImpl<B> {
+  type Error = I2cError;
+
+  fn write_read(
+    &mut self,
+    address: u8,
+    bytes: &[u8],
+    buffer: &mut [u8],
+  ) -> Result<(), Self::Error> {
+    self.is_initializied()?;
+    interface::write_read(&self.backend, &self.timing(), address.into(), bytes, buffer).map(|_| ())
+  }
+}
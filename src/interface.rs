@@ -11,371 +11,829 @@
 //!
 extern crate alloc;
 use alloc::{vec, vec::Vec};
+use core::time::Duration;
 
-use ruspiro_gpio::GPIO;
-use ruspiro_mmio_register::define_mmio_register;
-use ruspiro_timer as timer;
+use embedded_hal::i2c::Operation;
+use ruspiro_gpio::{Gpio, GPIO};
 
-#[cfg(feature = "ruspiro_pi3")]
-const PERIPHERAL_BASE: usize = 0x3F00_0000;
+use crate::backend::{control, status, BscBackend, BscRegister, Line, Wire};
+use crate::controller::{AltFunction, BscPins};
+use crate::{Address, I2cConfig, I2cError, I2cResult, Probe, ScanConfig, SevenBitAddress};
 
-const I2C_BASE: usize = PERIPHERAL_BASE + 0x0080_4000; // I²C peripheral register base address
-const I2C_MAX_BYTES: usize = 16; // max FiFo size of the I²C peripheral
-const I2C_DEFAULT_WAIT: u32 = 2000; // max cycles to wait for a device to acknowledge a request
+pub(crate) const I2C_MAX_DLEN: usize = 0xFFFF; // max number of bytes a single transfer could handle
+const I2C_TIMEOUT_MARGIN: Duration = Duration::from_millis(1); // time added to a calculated transfer timeout
 
-use crate::I2cResult;
+/// The timing of the bus the timeout of a transfer is derived from
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Timing {
+  /// The timeout of a transfer. If not given it's calculated from the frequency and the transfer length.
+  pub(crate) timeout: Option<Duration>,
+  /// The actual bus frequency
+  pub(crate) frequency: u32,
+  /// The clock stretch timeout in SCL clock cycles, 0 if disabled
+  pub(crate) stretch: u32,
+  /// Recover the bus once a transfer has timed out
+  pub(crate) recover: bool,
+}
 
-pub(crate) fn initialize(core_speed: u32, fast_mode: bool) -> I2cResult<()> {
-  // when I2C is about to be initialized reserve GPIO Pins 2 and 3
-  // as the I2C bus pins with alt function 0
-  GPIO
-    .with_mut(|gpio| {
-      let _ = gpio.get_pin(2).map(|pin| pin.into_alt_f0());
-      let _ = gpio.get_pin(3).map(|pin| pin.into_alt_f0());
-      Ok(())
+impl Timing {
+  /// The time a transfer of the given number of bytes and address phases may take
+  pub(crate) fn timeout(&self, bytes: usize, addresses: usize) -> Duration {
+    self.timeout.unwrap_or_else(|| {
+      // each byte takes 9 clock cycles including the acknowledge bit. The device may stretch each of those cycles
+      // up to the clock stretch timeout, if disabled the transfer is allowed to take twice the time
+      let cycles = ((bytes + addresses) * 9) as u64 * (1 + self.stretch.max(1) as u64);
+      let frequency = self.frequency.max(1) as u64;
+      Duration::from_micros(cycles * 1_000_000 / frequency) + I2C_TIMEOUT_MARGIN
     })
-    .and_then(|_| {
-      // both pin's configured, now setup the I2C speed and we are done
-      let clock_divisor = if fast_mode {
-        core_speed / 400_000
-      } else {
-        core_speed / 100_000
-      };
+  }
 
-      I2C_REG_CDIV::Register.set(clock_divisor);
-      Ok(())
-    })
+  /// The time of half a clock cycle of the bus
+  fn half_period(&self) -> Duration {
+    Duration::from_nanos(500_000_000 / self.frequency.max(1) as u64)
+  }
+}
+
+pub(crate) fn initialize<B: BscBackend>(bsc: &B, config: &I2cConfig) -> I2cResult<u32> {
+  // check the configuration before any pin is claimed
+  config.timeout_cycles()?;
+  config.data_delay_value()?;
+  // when I2C is about to be initialized reserve the GPIO Pins the controller is connected to
+  // as the I2C bus pins with the alt function connecting them to the controller
+  if let Some(pins) = bsc.pins() {
+    GPIO.with_mut(|gpio| claim_pins(gpio, &pins))?;
+  }
+
+  // both pin's configured, now setup the I2C speed and we are done
+  configure(bsc, config)
+}
+
+/// Reserve the GPIO pins of the controller with the alt function connecting them to the controller
+fn claim_pins(gpio: &mut Gpio, pins: &BscPins) -> I2cResult<()> {
+  for &pin in [pins.sda, pins.scl].iter() {
+    let claimed = gpio.get_pin(pin).map(|pin| match pins.alt_function {
+      AltFunction::Alt0 => {
+        pin.into_alt_f0();
+      }
+      AltFunction::Alt5 => {
+        pin.into_alt_f5();
+      }
+    });
+    if claimed.is_err() {
+      // release the first pin again as the bus could not be used without the second one
+      if pin == pins.scl {
+        gpio.free_pin(pins.sda);
+      }
+      return Err(I2cError::PinUnavailable { pin });
+    }
+  }
+  Ok(())
 }
 
-/// Scan for I2C devices currently connected to the I2C bus. The scan will just try to get an acknowledge message
-/// from any slave address between 0x00 and 0x7F. If a device is connected this call succeeds and the corresponding
-/// address is written to the console
-pub(crate) fn scan_devices() -> Vec<u8> {
-  let mut r: Vec<u8> = vec![];
-
-  for addr in 0x00..0x80 {
-    I2C_REG_A::Register.set(addr);
-    I2C_REG_DLEN::Register.set(1);
-    I2C_REG_S::Register.write_value(
-      I2C_REG_S::CLK_TIMEOUT::SET | I2C_REG_S::ACK_ERROR::SET | I2C_REG_S::TRANS_DONE::SET,
-    );
-    I2C_REG_C::Register.write_value(
-      I2C_REG_C::ENABLE::SET
-        | I2C_REG_C::STARTTRANS::SET
-        | I2C_REG_C::FIFO_CLR::CLEAR
-        | I2C_REG_C::READWRITE::READ,
-    );
-
-    if wait_i2c_done(100).is_ok() {
-      r.push(addr as u8);
+/// Release a device holding the SDA line low, e.g. after it has been reset in the middle of a transfer. The pins are
+/// taken from the controller and SCL is clocked up to nine times until the device releases SDA, followed by a STOP
+/// condition. Afterwards the pins are handed back to the controller. A backend without GPIO pins recovers the bus
+/// itself if it drives the bus lines.
+pub(crate) fn recover_bus<B: BscBackend>(bsc: &B, timing: &Timing) -> I2cResult<()> {
+  // abort the transfer that might still be active on the controller
+  bsc.write(BscRegister::Control, control::FIFO_CLR);
+  clear_status(bsc);
+  let pins = match bsc.pins() {
+    Some(pins) => pins,
+    None => return bsc.recover_bus(),
+  };
+
+  let released = GPIO.with_mut(|gpio| {
+    gpio.free_pin(pins.sda);
+    gpio.free_pin(pins.scl);
+    // both lines are driven open drain, so the device is able to hold them low all the time
+    let scl = match gpio.get_pin(pins.scl) {
+      Ok(pin) => Line::Released(pin.into_pud_up().into_input()),
+      Err(_) => return Err(reclaim_pins(gpio, &pins, pins.scl)),
+    };
+    let sda = match gpio.get_pin(pins.sda) {
+      Ok(pin) => Line::Released(pin.into_pud_up().into_input()),
+      Err(_) => {
+        gpio.free_pin(pins.scl);
+        return Err(reclaim_pins(gpio, &pins, pins.sda));
+      }
     };
+    let released = Wire::new(sda, scl, timing.half_period()).recover();
+
+    // hand the pins back to the controller
+    gpio.free_pin(pins.sda);
+    gpio.free_pin(pins.scl);
+    claim_pins(gpio, &pins)?;
+    Ok(released)
+  })?;
+
+  if released {
+    Ok(())
+  } else {
+    Err(I2cError::BusStuck)
   }
+}
 
-  r
+/// Hand the pins back to the controller once the pin taken from it for the bus recovery has not been available, so
+/// the controller keeps working with the bus as before
+fn reclaim_pins(gpio: &mut Gpio, pins: &BscPins, unavailable: u32) -> I2cError {
+  match claim_pins(gpio, pins) {
+    Ok(()) => I2cError::PinUnavailable { pin: unavailable },
+    Err(error) => error,
+  }
 }
 
-pub(crate) fn check_device(addr: u8) -> I2cResult<()> {
-  I2C_REG_A::Register.set(addr as u32);
-  I2C_REG_DLEN::Register.set(1);
-  I2C_REG_S::Register.write_value(
-    I2C_REG_S::CLK_TIMEOUT::SET | I2C_REG_S::ACK_ERROR::SET | I2C_REG_S::TRANS_DONE::SET,
-  );
-  I2C_REG_C::Register.write_value(
-    I2C_REG_C::ENABLE::SET
-      | I2C_REG_C::STARTTRANS::SET
-      | I2C_REG_C::FIFO_CLR::CLEAR
-      | I2C_REG_C::READWRITE::READ,
-  );
+/// Setup the I2C speed, clock stretch timeout and data delay according to the configuration. Nothing is changed if
+/// any part of the configuration is invalid. Returns the actual bus frequency.
+pub(crate) fn configure<B: BscBackend>(bsc: &B, config: &I2cConfig) -> I2cResult<u32> {
+  let clock_divisor = config.clock_divisor()?;
+  let timeout = config.timeout_cycles()?;
+  let delay = config.data_delay_value()?;
+  bsc.write(BscRegister::ClockDivisor, clock_divisor);
+  bsc.write(BscRegister::DataDelay, delay);
+  bsc.write(BscRegister::ClockStretchTimeout, timeout);
+  Ok(config.core_speed / clock_divisor)
+}
+
+/// Scan for I2C devices currently connected to the I2C bus. Each address within the configured range is probed in
+/// the configured way, the reserved addresses are skipped unless requested. A probe failing for another reason than
+/// a missing acknowledge or a timeout aborts the scan.
+pub(crate) fn scan_devices<B: BscBackend>(
+  bsc: &B,
+  timing: &Timing,
+  config: &ScanConfig,
+) -> I2cResult<Vec<(SevenBitAddress, Probe)>> {
+  let timing = Timing {
+    timeout: config.timeout.or(timing.timeout),
+    ..*timing
+  };
+  let mut r = vec![];
+
+  for addr in (config.first..=config.last).filter_map(|addr| SevenBitAddress::new(addr).ok()) {
+    if config.include_reserved || !addr.is_reserved() {
+      let probe = probe_device(bsc, &timing, addr, config.mode.reads(addr.value()))?;
+      r.push((addr, probe));
+    }
+  }
 
-  wait_i2c_done(100)
+  Ok(r)
 }
 
-pub(crate) fn read_reg_u8(addr: u8, reg: u8) -> I2cResult<u8> {
-  // reading I²C device regiser data means:
-  // 1. write the register address to the device and wait for acknowledge
-  // 2. read from the device and wait for acknowledge
-  // 3. data available in the fifo
-  write_register(addr, reg)?;
-  I2C_REG_DLEN::Register.set(1);
-  I2C_REG_S::Register.write_value(
-    I2C_REG_S::CLK_TIMEOUT::SET | I2C_REG_S::ACK_ERROR::SET | I2C_REG_S::TRANS_DONE::SET,
-  );
-  I2C_REG_C::Register.write_value(
-    I2C_REG_C::ENABLE::SET
-      | I2C_REG_C::STARTTRANS::SET
-      | I2C_REG_C::FIFO_CLR::CLEAR
-      | I2C_REG_C::READWRITE::READ,
-  );
-  wait_i2c_done(I2C_DEFAULT_WAIT)?;
+/// Probe a single address with a read of one byte or a Quick Write
+fn probe_device<B: BscBackend>(
+  bsc: &B,
+  timing: &Timing,
+  addr: SevenBitAddress,
+  read: bool,
+) -> I2cResult<Probe> {
+  let result = if read {
+    check_device(bsc, timing, addr.into())
+  } else {
+    write_data(bsc, timing, addr.into(), &[])
+  };
+  match result {
+    Ok(()) => Ok(Probe::Acknowledged),
+    Err(I2cError::AddressNack { .. }) => Ok(Probe::Absent),
+    Err(I2cError::Timeout { .. }) | Err(I2cError::ClockStretchTimeout { .. }) => Ok(Probe::Busy),
+    Err(error) => Err(error),
+  }
+}
+
+/// Scan for I2C devices with a 10 bit address between 0x000 and 0x3FF
+pub(crate) fn scan_ten_bit_devices<B: BscBackend>(bsc: &B, timing: &Timing) -> Vec<u16> {
+  (0x000..0x400)
+    .filter(|&addr| check_device(bsc, timing, Address::TenBit(addr)).is_ok())
+    .collect()
+}
+
+pub(crate) fn check_device<B: BscBackend>(
+  bsc: &B,
+  timing: &Timing,
+  addr: Address,
+) -> I2cResult<()> {
+  let mut data: [u8; 1] = [0; 1];
+  transfer(
+    bsc,
+    timing,
+    addr,
+    &mut [Segment::Read(1, &mut data.iter_mut())],
+  )
+}
+
+pub(crate) fn read_reg_u8<B: BscBackend>(
+  bsc: &B,
+  timing: &Timing,
+  addr: Address,
+  reg: u8,
+) -> I2cResult<u8> {
   let mut buff: [u8; 1] = [0; 1];
-  read_fifo(&mut buff);
+  read_reg_data(bsc, timing, addr, reg, &mut buff)?;
   Ok(buff[0])
 }
 
-pub(crate) fn read_reg_u16(addr: u8, reg: u8) -> I2cResult<u16> {
+pub(crate) fn read_reg_u16<B: BscBackend>(
+  bsc: &B,
+  timing: &Timing,
+  addr: Address,
+  reg: u8,
+) -> I2cResult<u16> {
   let mut buff: [u8; 2] = [0; 2];
-  read_reg_data(addr, reg, &mut buff)?;
+  read_reg_data(bsc, timing, addr, reg, &mut buff)?;
   Ok((buff[0] as u16) << 8 | (buff[1] as u16))
 }
 
-pub(crate) fn read_reg_data(addr: u8, reg: u8, buffer: &mut [u8]) -> I2cResult<usize> {
+pub(crate) fn read_reg_data<B: BscBackend>(
+  bsc: &B,
+  timing: &Timing,
+  addr: Address,
+  reg: u8,
+  buffer: &mut [u8],
+) -> I2cResult<usize> {
   // reading I²C device regiser data means:
-  // 1. write the register address to the device and wait for acknowledge
-  // 2. read from the device and wait for acknowledge
+  // 1. write the register address to the device
+  // 2. read from the device after a repeated start condition
   // 3. data available in the fifo
-  write_register(addr, reg)?;
-  I2C_REG_DLEN::Register.set(buffer.len() as u32);
-  I2C_REG_S::Register.write_value(
-    I2C_REG_S::CLK_TIMEOUT::SET | I2C_REG_S::ACK_ERROR::SET | I2C_REG_S::TRANS_DONE::SET,
-  );
-  I2C_REG_C::Register.write_value(
-    I2C_REG_C::ENABLE::SET
-      | I2C_REG_C::STARTTRANS::SET
-      | I2C_REG_C::FIFO_CLR::CLEAR
-      | I2C_REG_C::READWRITE::READ,
-  );
-  wait_i2c_done(I2C_DEFAULT_WAIT)?;
-  //let mut data: Vec<u8> = Vec::with_capacity(count as usize);
-  let chunks = buffer.len() / I2C_MAX_BYTES;
-  let mut remainder = buffer.len();
-  for c in 0..chunks + 1 {
-    let start = c * I2C_MAX_BYTES;
-    let size = if remainder > I2C_MAX_BYTES {
-      I2C_MAX_BYTES
+  write_read(bsc, timing, addr, &[reg], buffer)
+}
+
+/// Write data to a device and read data from it in one combined transfer. The read is started with a repeated
+/// START condition without releasing the bus with a STOP condition in between.
+pub(crate) fn write_read<B: BscBackend>(
+  bsc: &B,
+  timing: &Timing,
+  addr: Address,
+  wbuf: &[u8],
+  rbuf: &mut [u8],
+) -> I2cResult<usize> {
+  transfer(
+    bsc,
+    timing,
+    addr,
+    &mut [
+      Segment::Write(wbuf.len(), &mut wbuf.iter().copied()),
+      Segment::Read(rbuf.len(), &mut rbuf.iter_mut()),
+    ],
+  )?;
+  Ok(rbuf.len())
+}
+
+pub(crate) fn write_raw_u8<B: BscBackend>(
+  bsc: &B,
+  timing: &Timing,
+  addr: Address,
+  data: u8,
+) -> I2cResult<()> {
+  write_data(bsc, timing, addr, &[data])
+}
+
+pub(crate) fn write_reg_u8<B: BscBackend>(
+  bsc: &B,
+  timing: &Timing,
+  addr: Address,
+  reg: u8,
+  data: u8,
+) -> I2cResult<()> {
+  write_data(bsc, timing, addr, &[reg, data])
+}
+
+pub(crate) fn write_reg_u16<B: BscBackend>(
+  bsc: &B,
+  timing: &Timing,
+  addr: Address,
+  reg: u8,
+  data: u16,
+) -> I2cResult<()> {
+  let buffer: [u8; 2] = [(data >> 8) as u8, (data & 0xFF) as u8];
+  write_reg_data(bsc, timing, addr, reg, &buffer)
+}
+
+pub(crate) fn write_reg_data<B: BscBackend>(
+  bsc: &B,
+  timing: &Timing,
+  addr: Address,
+  reg: u8,
+  data: &[u8],
+) -> I2cResult<()> {
+  // the register id is send as the first byte followed by the data
+  let mut bytes = core::iter::once(reg).chain(data.iter().copied());
+  transfer(
+    bsc,
+    timing,
+    addr,
+    &mut [Segment::Write(data.len() + 1, &mut bytes)],
+  )
+}
+
+/// Write the data buffer to a device
+pub(crate) fn write_data<B: BscBackend>(
+  bsc: &B,
+  timing: &Timing,
+  addr: Address,
+  data: &[u8],
+) -> I2cResult<()> {
+  transfer(
+    bsc,
+    timing,
+    addr,
+    &mut [Segment::Write(data.len(), &mut data.iter().copied())],
+  )
+}
+
+/// Read data from a device into the buffer
+pub(crate) fn read_data<B: BscBackend>(
+  bsc: &B,
+  timing: &Timing,
+  addr: Address,
+  buffer: &mut [u8],
+) -> I2cResult<usize> {
+  transfer(
+    bsc,
+    timing,
+    addr,
+    &mut [Segment::Read(buffer.len(), &mut buffer.iter_mut())],
+  )?;
+  Ok(buffer.len())
+}
+
+/// Process the operations of an ``embedded-hal`` transaction. Consecutive operations of the same kind are merged
+/// into one segment without any START condition in between.
+pub(crate) fn transaction<B: BscBackend>(
+  bsc: &B,
+  timing: &Timing,
+  addr: Address,
+  operations: &mut [Operation<'_>],
+) -> I2cResult<()> {
+  check_transaction(operations)?;
+  if operations.is_empty() {
+    return Ok(());
+  }
+  let mut writes: Vec<WriteOperations> = Vec::new();
+  let mut reads: Vec<ReadOperations> = Vec::new();
+  let mut runs: Vec<(bool, usize)> = Vec::new();
+  let mut rest = operations;
+  while !rest.is_empty() {
+    let read = matches!(rest[0], Operation::Read(_));
+    let count = rest
+      .iter()
+      .take_while(|op| matches!(op, Operation::Read(_)) == read)
+      .count();
+    let (run, tail) = core::mem::take(&mut rest).split_at_mut(count);
+    let len = run
+      .iter()
+      .map(|op| match op {
+        Operation::Read(buffer) => buffer.len(),
+        Operation::Write(data) => data.len(),
+      })
+      .sum();
+    if read {
+      reads.push(ReadOperations::new(run));
+    } else {
+      writes.push(WriteOperations::new(run));
+    }
+    runs.push((read, len));
+    rest = tail;
+  }
+
+  let mut writes = writes.iter_mut();
+  let mut reads = reads.iter_mut();
+  let mut segments: Vec<Segment> = Vec::with_capacity(runs.len());
+  for (read, len) in runs {
+    // there is an iterator for each run of operations
+    let segment = if read {
+      reads.next().map(|ops| Segment::Read(len, ops))
     } else {
-      remainder
+      writes.next().map(|ops| Segment::Write(len, ops))
     };
-    read_fifo(&mut buffer[start..start + size]);
-    remainder -= I2C_MAX_BYTES;
+    segments.extend(segment);
   }
-  Ok(buffer.len())
+  transfer(bsc, timing, addr, &mut segments)
 }
 
-pub(crate) fn write_raw_u8(addr: u8, data: u8) -> I2cResult<()> {
-  // clear status flags
-  I2C_REG_S::Register.write_value(
-    I2C_REG_S::CLK_TIMEOUT::SET | I2C_REG_S::ACK_ERROR::SET | I2C_REG_S::TRANS_DONE::SET,
-  );
-  // clear FiFo data in case FiFo data has remained from previous calls
-  I2C_REG_C::Register.write(I2C_REG_C::FIFO_CLR, 1);
-  // set the slave address we would like to send data to and the register id
-  I2C_REG_A::Register.set(addr as u32);
-  I2C_REG_DLEN::Register.set(1);
-  I2C_REG_FIFO::Register.set(data as u32);
-  // transmit the data
-  I2C_REG_C::Register
-    .write_value(I2C_REG_C::ENABLE::SET | I2C_REG_C::STARTTRANS::SET | I2C_REG_C::READWRITE::WRITE);
+/// Reject a transaction with a write following a read. The BSC controller joins a read following a write with a
+/// repeated START condition only, any other operation would be separated by a STOP condition the device might lose
+/// its state with.
+pub(crate) fn check_transaction(operations: &[Operation<'_>]) -> I2cResult<()> {
+  let mut read = false;
+  for operation in operations.iter() {
+    match operation {
+      Operation::Read(_) => read = true,
+      Operation::Write(_) if read => return Err(I2cError::UnsupportedTransaction),
+      Operation::Write(_) => (),
+    }
+  }
+  Ok(())
+}
 
-  wait_i2c_done(I2C_DEFAULT_WAIT)
+/// Iterate over the bytes of consecutive write operations
+struct WriteOperations<'o, 'b> {
+  operations: core::slice::Iter<'o, Operation<'b>>,
+  current: core::slice::Iter<'b, u8>,
 }
 
-pub(crate) fn write_reg_u8(addr: u8, reg: u8, data: u8) -> I2cResult<()> {
-  // clear status flags
-  I2C_REG_S::Register.write_value(
-    I2C_REG_S::CLK_TIMEOUT::SET | I2C_REG_S::ACK_ERROR::SET | I2C_REG_S::TRANS_DONE::SET,
-  );
-  // clear FiFo data in case FiFo data has remained from previous calls
-  I2C_REG_C::Register.write(I2C_REG_C::FIFO_CLR, 1);
-  // set the slave address we would like to send data to and the register id
-  I2C_REG_A::Register.set(addr as u32);
-  I2C_REG_DLEN::Register.set(2);
-  I2C_REG_FIFO::Register.set(reg as u32);
-  I2C_REG_FIFO::Register.set(data as u32);
-  // transmit the data
-  I2C_REG_C::Register
-    .write_value(I2C_REG_C::ENABLE::SET | I2C_REG_C::STARTTRANS::SET | I2C_REG_C::READWRITE::WRITE);
+impl<'o, 'b> WriteOperations<'o, 'b> {
+  fn new(operations: &'o [Operation<'b>]) -> Self {
+    WriteOperations {
+      operations: operations.iter(),
+      current: [].iter(),
+    }
+  }
+}
 
-  wait_i2c_done(I2C_DEFAULT_WAIT)
+impl Iterator for WriteOperations<'_, '_> {
+  type Item = u8;
+
+  fn next(&mut self) -> Option<u8> {
+    loop {
+      if let Some(&data) = self.current.next() {
+        return Some(data);
+      }
+      match self.operations.next()? {
+        Operation::Write(data) => self.current = data.iter(),
+        Operation::Read(_) => (),
+      }
+    }
+  }
 }
 
-pub(crate) fn write_reg_u16(addr: u8, reg: u8, data: u16) -> I2cResult<()> {
-  let buffer: [u8; 2] = [(data >> 8) as u8, (data & 0xFF) as u8];
-  write_reg_data(addr, reg, &buffer)
+/// Iterate over the bytes of the buffers of consecutive read operations
+struct ReadOperations<'o, 'b> {
+  operations: core::slice::IterMut<'o, Operation<'b>>,
+  current: core::slice::IterMut<'o, u8>,
 }
 
-pub(crate) fn write_reg_data(addr: u8, reg: u8, data: &[u8]) -> I2cResult<()> {
-  let mut data_len = data.len();
-  // clear status flags
-  I2C_REG_S::Register.write_value(
-    I2C_REG_S::CLK_TIMEOUT::SET | I2C_REG_S::ACK_ERROR::SET | I2C_REG_S::TRANS_DONE::SET,
-  );
-  // clear FiFo data in case FiFo data has remained from previous calls
-  I2C_REG_C::Register.write(I2C_REG_C::FIFO_CLR, 1);
-  // set the slave address we would like to send data to and the register id
-  I2C_REG_A::Register.set(addr as u32);
-  I2C_REG_DLEN::Register.set((data_len + 1) as u32);
-  I2C_REG_FIFO::Register.set(reg as u32);
-  // transmit the data
-  I2C_REG_C::Register
-    .write_value(I2C_REG_C::ENABLE::SET | I2C_REG_C::STARTTRANS::SET | I2C_REG_C::READWRITE::WRITE);
-  let chunks = data_len / I2C_MAX_BYTES;
-  for chunk in 0..chunks + 1 {
-    let idx = chunk * data_len;
-    let len = if data_len > I2C_MAX_BYTES {
-      I2C_MAX_BYTES
-    } else {
-      data_len
-    };
-    write_fifo(&data[idx..len]);
-    data_len -= I2C_MAX_BYTES;
+impl<'o, 'b> ReadOperations<'o, 'b> {
+  fn new(operations: &'o mut [Operation<'b>]) -> Self {
+    ReadOperations {
+      operations: operations.iter_mut(),
+      current: [].iter_mut(),
+    }
+  }
+}
+
+impl<'o> Iterator for ReadOperations<'o, '_> {
+  type Item = &'o mut u8;
+
+  fn next(&mut self) -> Option<&'o mut u8> {
+    loop {
+      if let Some(slot) = self.current.next() {
+        return Some(slot);
+      }
+      match self.operations.next()? {
+        Operation::Read(buffer) => self.current = buffer.iter_mut(),
+        Operation::Write(_) => (),
+      }
+    }
   }
+}
 
-  wait_i2c_done(I2C_DEFAULT_WAIT)
+/// One part of a transfer that is processed in a single direction. Consecutive segments are joined with a repeated
+/// START condition.
+pub(crate) enum Segment<'a, 'b> {
+  /// Write the given number of bytes taken from the iterator
+  Write(usize, &'a mut dyn Iterator<Item = u8>),
+  /// Read the given number of bytes into the slots provided by the iterator
+  Read(usize, &'a mut dyn Iterator<Item = &'b mut u8>),
 }
 
-/// Wait until the current I2C operation has been finished/acknowledged
-/// Returns an [Err] in case of a timeout or not beein acknowledged
-fn wait_i2c_done(tries: u32) -> I2cResult<()> {
-  for _ in 0..tries {
-    if I2C_REG_S::Register.read(I2C_REG_S::TRANS_DONE) != 0 {
-      if I2C_REG_S::Register.read(I2C_REG_S::ACK_ERROR) == 0 {
-        return Ok(());
-      } else {
-        return Err("I2C transmit not acknowledged");
+impl Segment<'_, '_> {
+  fn len(&self) -> usize {
+    match self {
+      Segment::Write(len, _) | Segment::Read(len, _) => *len,
+    }
+  }
+
+  fn is_read(&self) -> bool {
+    matches!(self, Segment::Read(..))
+  }
+}
+
+/// The part of a segment that is processed by the controller with a single setting of the data length register
+#[derive(Clone, Copy)]
+struct Chunk {
+  seg: usize,
+  read: bool,
+  len: usize,
+  // the position of the first byte of this chunk within all bytes written by the transfer
+  offset: usize,
+}
+
+/// Splits the segments of a transfer into the chunks the controller is able to handle
+#[derive(Default)]
+struct Chunks {
+  seg: usize,
+  done: usize,
+  written: usize,
+}
+
+impl Chunks {
+  fn next(&mut self, segments: &[Segment<'_, '_>]) -> Option<Chunk> {
+    while let Some(segment) = segments.get(self.seg) {
+      let left = segment.len() - self.done;
+      if left == 0 {
+        // empty segments are skipped
+        self.seg += 1;
+        self.done = 0;
+        continue;
       }
+      let chunk = Chunk {
+        seg: self.seg,
+        read: segment.is_read(),
+        len: left.min(I2C_MAX_DLEN),
+        offset: self.written,
+      };
+      self.done += chunk.len;
+      if !chunk.read {
+        self.written += chunk.len;
+      }
+      return Some(chunk);
     }
-    timer::sleepcycles(1000);
+    None
   }
-  Err("time out waiting for I2C transmit")
-}
-
-/// Write the register to the I2C device we would like to access next (e.g. write to)
-fn write_register(addr: u8, reg: u8) -> I2cResult<()> {
-  // set the slave address we would like to send data to and the register id
-  I2C_REG_A::Register.set(addr.into());
-  I2C_REG_DLEN::Register.set(1);
-  I2C_REG_FIFO::Register.set(reg.into());
-  // transmit the data
-  I2C_REG_S::Register.write_value(
-    I2C_REG_S::CLK_TIMEOUT::SET | I2C_REG_S::ACK_ERROR::SET | I2C_REG_S::TRANS_DONE::SET,
-  );
-  I2C_REG_C::Register
-    .write_value(I2C_REG_C::ENABLE::SET | I2C_REG_C::STARTTRANS::SET | I2C_REG_C::READWRITE::WRITE);
+}
 
-  wait_i2c_done(I2C_DEFAULT_WAIT)
+/// Process a transfer made of the given segments to the device with the given address. Each write to a 10 bit device
+/// starts with the lower 8 bits of its address and a read needs to be preceded by such a write.
+pub(crate) fn transfer<B: BscBackend>(
+  bsc: &B,
+  timing: &Timing,
+  addr: Address,
+  segments: &mut [Segment<'_, '_>],
+) -> I2cResult<()> {
+  addr.validate()?;
+  let low = match addr.low_byte() {
+    Some(low) => low,
+    None => return transfer_segments(bsc, timing, addr.header(), segments),
+  };
+
+  let mut address = core::iter::once(low);
+  let mut writes: Vec<Prefixed> = Vec::new();
+  let mut reads: Vec<(usize, &mut dyn Iterator<Item = &mut u8>)> = Vec::new();
+  let mut order: Vec<bool> = Vec::with_capacity(segments.len());
+  let mut prefixes: Vec<usize> = Vec::new();
+  let mut written = 0;
+  if !matches!(segments.first(), Some(Segment::Write(..))) {
+    // the device is selected with its full address before reading from it
+    prefixes.push(0);
+    written += 1;
+  }
+  for segment in segments.iter_mut() {
+    match segment {
+      Segment::Write(len, data) => {
+        prefixes.push(written);
+        written += *len + 1;
+        writes.push(Prefixed {
+          low: Some(low),
+          len: *len + 1,
+          data: &mut **data,
+        });
+        order.push(false);
+      }
+      Segment::Read(len, slots) => {
+        reads.push((*len, &mut **slots));
+        order.push(true);
+      }
+    }
+  }
+
+  let mut writes = writes.iter_mut();
+  let mut reads = reads.iter_mut();
+  let mut prefixed: Vec<Segment> = Vec::with_capacity(order.len() + 1);
+  if prefixes.len() > writes.len() {
+    prefixed.push(Segment::Write(1, &mut address));
+  }
+  for read in order {
+    let segment = if read {
+      reads
+        .next()
+        .map(|(len, slots)| Segment::Read(*len, &mut **slots))
+    } else {
+      writes.next().map(|data| Segment::Write(data.len, data))
+    };
+    prefixed.extend(segment);
+  }
+  transfer_segments(bsc, timing, addr.header(), &mut prefixed)
+    .map_err(|error| addr.map_error(&prefixes, error))
 }
 
-/// Read the data from the I2C FIFO register
-fn read_fifo(buffer: &mut [u8]) -> usize {
-  //let mut data: Vec<u8> = Vec::with_capacity(count as usize);
-  let num = if buffer.len() > I2C_MAX_BYTES {
-    I2C_MAX_BYTES
-  } else {
-    buffer.len()
+/// The bytes written to a 10 bit device prefixed with the lower bits of its address
+struct Prefixed<'a> {
+  low: Option<u8>,
+  len: usize,
+  data: &'a mut dyn Iterator<Item = u8>,
+}
+
+impl Iterator for Prefixed<'_> {
+  type Item = u8;
+
+  fn next(&mut self) -> Option<u8> {
+    self.low.take().or_else(|| self.data.next())
+  }
+}
+
+/// Process a transfer made of the given segments. The controller FIFO is fed/drained while the transfer is active,
+/// so the length of the transfer is not limited by the FIFO size. Segments exceeding the maximum data length of
+/// the controller are split into several chunks. A read chunk following a write chunk is armed while the write is
+/// still active on the bus, so the controller continues with a repeated START condition instead of a STOP. The
+/// controller does not support this for any other chunk, which is therefore started once the previous one is done.
+/// The transfer is considered as timed out once it takes longer than the timeout given with the ``timing``.
+fn transfer_segments<B: BscBackend>(
+  bsc: &B,
+  timing: &Timing,
+  addr: u8,
+  segments: &mut [Segment<'_, '_>],
+) -> I2cResult<()> {
+  // each chunk of the transfer starts with the address of the device
+  let bytes: usize = segments.iter().map(Segment::len).sum();
+  let addresses = segments.len().max(1) + bytes / I2C_MAX_DLEN;
+  let deadline = bsc.now() + timing.timeout(bytes, addresses);
+  let mut chunks = Chunks::default();
+  let mut current = match chunks.next(segments) {
+    Some(chunk) => chunk,
+    // a transfer without any data just addresses the device (quick command)
+    None => Chunk {
+      seg: 0,
+      read: segments.first().map(Segment::is_read).unwrap_or(false),
+      len: 0,
+      offset: 0,
+    },
   };
-  for i in 0..num {
-    while I2C_REG_S::Register.read(I2C_REG_S::RX_DATA) == 0 {}
-    buffer[i] = (I2C_REG_FIFO::Register.get() & 0xFF) as u8;
+  let mut next = chunks.next(segments);
+  let mut armed = false;
+  let mut last_write = if current.read { None } else { Some(current) };
+  // the bytes of the current chunk not yet passed to or taken from the FiFo
+  let mut left = current.len;
+
+  // clear status flags
+  clear_status(bsc);
+  // clear FiFo data in case FiFo data has remained from previous calls
+  bsc.write(BscRegister::Control, control::FIFO_CLR);
+  bsc.write(BscRegister::SlaveAddress, addr as u32);
+  if !current.read {
+    // pre-fill the FiFo with the first bytes to write
+    left -= fill_fifo(bsc, segments, &current, left);
+  }
+  start_chunk(bsc, &current);
+
+  loop {
+    let state = bsc.read(BscRegister::Status);
+    let mut progress = 0;
+
+    // keep the FiFo fed while writing and drained while reading
+    if current.read {
+      progress += drain_fifo(bsc, segments, &current, left);
+    } else if state & status::TX_NEEDWRITE != 0 {
+      progress += fill_fifo(bsc, segments, &current, left);
+    }
+    left -= progress;
+
+    let finished = state & (status::CLK_TIMEOUT | status::TRANS_DONE) != 0;
+    if finished {
+      // the last bytes of a read are still in the FiFo once the transfer is done
+      if current.read {
+        let drained = drain_fifo(bsc, segments, &current, left);
+        left -= drained;
+        progress += drained;
+      }
+      transfer_result(
+        bsc,
+        addr,
+        last_write.map(|chunk| (chunk.offset, chunk.len)),
+        state,
+      )?;
+    }
+
+    if left == 0 {
+      match next {
+        // all chunks processed
+        None if finished => return Ok(()),
+        None => (),
+        Some(chunk) => {
+          if !armed && finished {
+            // the previous chunk has already been finished, so this is a transfer on its own
+            clear_status(bsc);
+            start_chunk(bsc, &chunk);
+            armed = true;
+          } else if !armed && !current.read && chunk.read && state & status::TRANS_ACTIVE != 0 {
+            // a read following a write is armed once all bytes are written to the FiFo, the controller continues
+            // with a repeated START. Any other chunk is started once the current one is done.
+            start_chunk(bsc, &chunk);
+            armed = true;
+          }
+          if armed {
+            // continue with the next chunk
+            current = chunk;
+            left = current.len;
+            next = chunks.next(segments);
+            armed = false;
+            if !current.read {
+              last_write = Some(current);
+            }
+            continue;
+          }
+        }
+      }
+    }
+
+    if progress == 0 {
+      if bsc.now() > deadline {
+        // abort the transfer still active on the controller
+        bsc.write(BscRegister::Control, control::FIFO_CLR);
+        if timing.recover {
+          // the timeout is reported even if the recovery has released the bus
+          let _ = recover_bus(bsc, timing);
+        }
+        return Err(I2cError::Timeout {
+          addr: Address::SevenBit(addr),
+        });
+      }
+      bsc.idle();
+    }
   }
-  num
 }
 
-/// Write a data buffer to the FIFO
-fn write_fifo(data: &[u8]) {
-  for i in 0..data.len() {
-    while I2C_REG_S::Register.read(I2C_REG_S::TX_DATA) == 0 {}
-    I2C_REG_FIFO::Register.set(data[i] as u32);
+/// Setup the data length and start the transfer of the chunk. If the chunk is armed while the previous one is still
+/// active the controller will start it with a repeated start condition once the previous one has finished.
+fn start_chunk<B: BscBackend>(bsc: &B, chunk: &Chunk) {
+  bsc.write(BscRegister::DataLength, chunk.len as u32);
+  let direction = if chunk.read { control::READ } else { 0 };
+  bsc.write(
+    BscRegister::Control,
+    control::ENABLE | control::STARTTRANS | direction,
+  );
+}
+
+/// Write up to ``left`` bytes of the chunk to the FiFo while it is able to accept data. Returns the number of bytes
+/// written.
+fn fill_fifo<B: BscBackend>(
+  bsc: &B,
+  segments: &mut [Segment<'_, '_>],
+  chunk: &Chunk,
+  left: usize,
+) -> usize {
+  let mut count = 0;
+  if let Segment::Write(_, bytes) = &mut segments[chunk.seg] {
+    while count < left && bsc.read(BscRegister::Status) & status::TX_DATA != 0 {
+      bsc.write(BscRegister::Fifo, bytes.next().unwrap_or(0) as u32);
+      count += 1;
+    }
   }
+  count
 }
 
-// I2C register definitions
-define_mmio_register!(
-    // control register
-    I2C_REG_C<ReadWrite<u32>@(I2C_BASE + 0x00)> {
-        // I²C bus enabled flag
-        ENABLE     OFFSET(15) [
-            SET = 1,
-            CLEAR = 0
-        ],
-        // Receive interrupt flag
-        IRQ_RX     OFFSET(10) [
-            SET = 1,
-            CLEAR = 0
-        ],
-        // Transmit interrupt flag
-        IRQ_TX     OFFSET(9) [
-            SET = 1,
-            CLEAR = 0
-        ],
-        // Done interrupt flag
-        IRQ_DONE   OFFSET(8) [
-            SET = 1,
-            CLEAR = 0
-        ],
-        // Start transfer flag
-        STARTTRANS OFFSET(7) [
-            SET = 1,
-            CLEAR = 0
-        ],
-        // clear fifo buffer
-        FIFO_CLR  OFFSET(4) [
-            CLEAR = 1,
-            KEEP = 0
-        ],
-        // Read / 0 Write operation
-        READWRITE  OFFSET(0) [
-            READ = 1,
-            WRITE = 0
-        ]
+/// Read up to ``left`` bytes of the chunk from the FiFo while it contains data. Returns the number of bytes read.
+fn drain_fifo<B: BscBackend>(
+  bsc: &B,
+  segments: &mut [Segment<'_, '_>],
+  chunk: &Chunk,
+  left: usize,
+) -> usize {
+  let mut count = 0;
+  if let Segment::Read(_, slots) = &mut segments[chunk.seg] {
+    while count < left && bsc.read(BscRegister::Status) & status::RX_DATA != 0 {
+      let data = (bsc.read(BscRegister::Fifo) & 0xFF) as u8;
+      if let Some(slot) = slots.next() {
+        *slot = data;
+      }
+      count += 1;
     }
-);
-
-define_mmio_register!(
-    // status register
-    I2C_REG_S<ReadWrite<u32>@(I2C_BASE + 0x04)> {
-        CLK_TIMEOUT  OFFSET(9) [
-            SET = 1,
-            CLEAR = 0
-        ], // 1 Slave has held the SCL signal longer than allowed high
-        ACK_ERROR    OFFSET(8) [
-            SET = 1,
-            CLEAR = 0
-        ], // 1 Slave address acknowledge error
-        RX_FULL      OFFSET(7) [
-            SET = 1,
-            CLEAR = 0
-        ], // 1 FIFO is full
-        TX_EMPTY     OFFSET(6) [
-            SET = 1,
-            CLEAR = 0
-        ], // 1 FIFO is empty
-        RX_DATA      OFFSET(5) [
-            SET = 1,
-            CLEAR = 0
-        ], // 1 FIFO contains at least one byte
-        TX_DATA      OFFSET(4) [
-            SET = 1,
-            CLEAR = 0
-        ], // 1 FIFO can accept data
-        RX_NEEDREAD  OFFSET(3) [
-            SET = 1,
-            CLEAR = 0
-        ], // 1 FIFO is full and needs reading from the FIFO
-        TX_NEEDWRITE OFFSET(2) [
-            SET = 1,
-            CLEAR = 0
-        ], // 1 FIFO is less than full and needs writing to the FIFO
-        TRANS_DONE   OFFSET(1) [
-            SET = 1,
-            CLEAR = 0
-        ], // 1 if transfer is complete
-        TRANS_ACTIVE OFFSET(0) [
-            SET = 1,
-            CLEAR = 0
-        ]  // 1 if transfer is active
-    },
-    // data len register
-    I2C_REG_DLEN<ReadWrite<u32>@(I2C_BASE + 0x08)> {
-        DATA OFFSET(0) BITS(16)
-    },
-    // slave address register
-    I2C_REG_A<ReadWrite<u32>@(I2C_BASE + 0x0C)>,
-    // FiFo data register
-    I2C_REG_FIFO<ReadWrite<u32>@(I2C_BASE + 0x10)>,
-    // clock divisor
-    I2C_REG_CDIV<ReadWrite<u32>@(I2C_BASE + 0x14)>,
-    // data delay
-    I2C_REG_DEL<ReadWrite<u32>@(I2C_BASE + 0x18)>,
-    // clock stretch timeout
-    I2C_REG_CLKT<ReadWrite<u32>@(I2C_BASE + 0x1C)>
-);
+  }
+  count
+}
+
+/// Clear the error and done flags of the status register
+pub(crate) fn clear_status<B: BscBackend>(bsc: &B) {
+  bsc.write(
+    BscRegister::Status,
+    status::CLK_TIMEOUT | status::ACK_ERROR | status::TRANS_DONE,
+  );
+}
+
+/// Evaluate the status of a finished transfer. The ``last_write`` is the offset and length of the last chunk written
+/// to the device and is used to figure out which byte has not been acknowledged by the device.
+pub(crate) fn transfer_result<B: BscBackend>(
+  bsc: &B,
+  addr: u8,
+  last_write: Option<(usize, usize)>,
+  state: u32,
+) -> I2cResult<()> {
+  if state & status::CLK_TIMEOUT != 0 {
+    return Err(I2cError::ClockStretchTimeout {
+      addr: Address::SevenBit(addr),
+    });
+  }
+  if state & status::ACK_ERROR == 0 {
+    return Ok(());
+  }
+  // the data length register counts down while bytes are transmitted. If nothing has been send the device
+  // rejected its address, otherwise the last byte handed to the bus was rejected
+  let remaining = (bsc.read(BscRegister::DataLength) & 0xFFFF) as usize;
+  match last_write {
+    Some((offset, len)) if remaining < len => Err(I2cError::DataNack {
+      addr: Address::SevenBit(addr),
+      index: offset + len - remaining - 1,
+    }),
+    _ => Err(I2cError::AddressNack {
+      addr: Address::SevenBit(addr),
+    }),
+  }
+}
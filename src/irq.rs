@@ -0,0 +1,468 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Interrupt driven transfers
+//!
+//! A transfer started in interrupt driven mode only sets up the controller and returns. The FIFO is serviced from the
+//! interrupt handler raised by the controller once it needs data to be written (``IRQ_TX``), contains data to be read
+//! (``IRQ_RX``) or has finished the transfer (``IRQ_DONE``).
+//!
+//! The transfer is kept in an [IrqSlot] shared between the thread that started it and the interrupt handler. The
+//! slot is claimed with an atomic flag instead of a lock, so the interrupt handler never waits for the thread. If the
+//! thread holds the slot while the interrupt is raised, the handler masks the interrupt of the controller and the
+//! thread services the transfer once it releases the slot.
+//!
+
+extern crate alloc;
+use alloc::{vec, vec::Vec};
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+use embedded_hal::i2c::Operation;
+
+use crate::asynch;
+use crate::backend::{control, status, BscBackend, BscRegister, MmioBackend};
+use crate::interface::{self, Timing, I2C_MAX_DLEN};
+use crate::{Address, Bsc, I2cError, I2cResult};
+
+const I2C_ARM_WAIT: u32 = 100; // max polls to wait for the transfer to become active before arming the next phase
+const IRQ_ALL: u32 = control::IRQ_RX | control::IRQ_TX | control::IRQ_DONE;
+
+/// Handle the interrupt raised by the BSC controllers. All BSC controllers share the same interrupt line, so this
+/// services the pending transfer of each controller, regardless whether it has been started with a static I²C bus
+/// accessor or an owned handle created with [crate::I2cImpl::new]. The handler does not take the lock of the static
+/// accessors, so it is safe to be raised while the bus is accessed within ``with_mut``.
+/// # Example
+/// ```no_run
+/// # use ruspiro_i2c::*;
+/// # fn doc() {
+///     // called by the interrupt manager for the I²C interrupt line
+///     handle_i2c_interrupt();
+/// # }
+/// ```
+pub fn handle_i2c_interrupt() {
+  for &bsc in CONTROLLERS.iter() {
+    controller_slot(bsc).service(&MmioBackend::new(bsc));
+    // wake the task waiting for an asynchronous transfer to progress
+    asynch::wake_task(bsc);
+  }
+}
+
+/// The BSC controllers sharing the I²C interrupt line
+#[cfg(not(feature = "ruspiro_pi4"))]
+const CONTROLLERS: [Bsc; 2] = [Bsc::Bsc0, Bsc::Bsc1];
+#[cfg(feature = "ruspiro_pi4")]
+const CONTROLLERS: [Bsc; 6] = [
+  Bsc::Bsc0,
+  Bsc::Bsc1,
+  Bsc::Bsc3,
+  Bsc::Bsc4,
+  Bsc::Bsc5,
+  Bsc::Bsc6,
+];
+
+static BSC0_SLOT: IrqSlot = IrqSlot::new();
+static BSC1_SLOT: IrqSlot = IrqSlot::new();
+#[cfg(feature = "ruspiro_pi4")]
+static BSC3_SLOT: IrqSlot = IrqSlot::new();
+#[cfg(feature = "ruspiro_pi4")]
+static BSC4_SLOT: IrqSlot = IrqSlot::new();
+#[cfg(feature = "ruspiro_pi4")]
+static BSC5_SLOT: IrqSlot = IrqSlot::new();
+#[cfg(feature = "ruspiro_pi4")]
+static BSC6_SLOT: IrqSlot = IrqSlot::new();
+
+/// The slot of the interrupt driven transfer of a BSC controller serviced by [handle_i2c_interrupt]
+pub(crate) fn controller_slot(bsc: Bsc) -> &'static IrqSlot {
+  match bsc {
+    Bsc::Bsc0 => &BSC0_SLOT,
+    Bsc::Bsc1 => &BSC1_SLOT,
+    #[cfg(feature = "ruspiro_pi4")]
+    Bsc::Bsc3 => &BSC3_SLOT,
+    #[cfg(feature = "ruspiro_pi4")]
+    Bsc::Bsc4 => &BSC4_SLOT,
+    #[cfg(feature = "ruspiro_pi4")]
+    Bsc::Bsc5 => &BSC5_SLOT,
+    #[cfg(feature = "ruspiro_pi4")]
+    Bsc::Bsc6 => &BSC6_SLOT,
+  }
+}
+
+/// The interrupt driven transfer of a bus shared between the thread and the interrupt handler. Whoever claims the
+/// slot has exclusive access to the transfer, the other side never waits for the slot to be released.
+pub(crate) struct IrqSlot {
+  claimed: AtomicBool,
+  // the interrupt handler found the slot claimed and left the interrupt of the controller masked
+  deferred: AtomicBool,
+  transfer: UnsafeCell<Option<IrqTransfer>>,
+}
+
+// the transfer is only accessed by the side that has claimed the slot
+unsafe impl Sync for IrqSlot {}
+
+impl IrqSlot {
+  pub(crate) const fn new() -> Self {
+    IrqSlot {
+      claimed: AtomicBool::new(false),
+      deferred: AtomicBool::new(false),
+      transfer: UnsafeCell::new(None),
+    }
+  }
+
+  /// Run the function with exclusive access to the transfer kept in the slot. Returns `None` without running the
+  /// function if the interrupt handler is servicing the transfer at the moment.
+  pub(crate) fn with<B: BscBackend, R>(
+    &self,
+    bsc: &B,
+    f: impl FnOnce(&mut Option<IrqTransfer>) -> R,
+  ) -> Option<R> {
+    if !self.claim() {
+      return None;
+    }
+    let result = f(unsafe { &mut *self.transfer.get() });
+    self.release(bsc);
+    Some(result)
+  }
+
+  /// Service the transfer kept in the slot from the interrupt of the controller. If the slot is claimed by the
+  /// thread the interrupt is masked and the transfer is serviced once the thread releases the slot.
+  pub(crate) fn service<B: BscBackend>(&self, bsc: &B) {
+    if !self.claim() {
+      // the controller keeps raising its interrupt until it is serviced
+      self.deferred.store(true, Ordering::SeqCst);
+      mask_interrupts(bsc);
+      // the thread might have released the slot before it was able to see the deferred service
+      if !self.claim() {
+        return;
+      }
+      self.deferred.store(false, Ordering::SeqCst);
+    }
+    match unsafe { &mut *self.transfer.get() } {
+      Some(transfer) => {
+        transfer.service(bsc);
+        transfer.resume(bsc);
+      }
+      // there is no transfer the interrupt could be raised for
+      None if bsc.read(BscRegister::Control) & IRQ_ALL != 0 => mask_interrupts(bsc),
+      None => (),
+    }
+    self.release(bsc);
+  }
+
+  fn claim(&self) -> bool {
+    self
+      .claimed
+      .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+      .is_ok()
+  }
+
+  fn release<B: BscBackend>(&self, bsc: &B) {
+    self.claimed.store(false, Ordering::SeqCst);
+    if self.deferred.swap(false, Ordering::SeqCst) {
+      self.service(bsc);
+    }
+  }
+}
+
+/// One phase of an interrupt driven transfer processed in a single direction. A read phase following a write phase
+/// is joined with a repeated START condition, any other phase is started once the previous one is done.
+pub(crate) struct Phase {
+  read: bool,
+  data: Vec<u8>,
+}
+
+impl Phase {
+  /// A phase writing the data to the device
+  pub(crate) fn write(data: Vec<u8>) -> Self {
+    Phase { read: false, data }
+  }
+
+  /// A phase reading ``len`` bytes from the device
+  pub(crate) fn read(len: usize) -> Self {
+    Phase {
+      read: true,
+      data: vec![0; len],
+    }
+  }
+}
+
+/// Merge consecutive operations of an ``embedded-hal`` transaction of the same kind into one phase
+pub(crate) fn phases(operations: &[Operation<'_>]) -> Vec<Phase> {
+  let mut phases: Vec<Phase> = Vec::new();
+  for operation in operations.iter() {
+    match (operation, phases.last_mut()) {
+      (Operation::Write(data), Some(phase)) if !phase.read => phase.data.extend_from_slice(data),
+      (Operation::Write(data), _) => phases.push(Phase::write(data.to_vec())),
+      (Operation::Read(buffer), Some(phase)) if phase.read => {
+        let len = phase.data.len() + buffer.len();
+        phase.data.resize(len, 0);
+      }
+      (Operation::Read(buffer), _) => phases.push(Phase::read(buffer.len())),
+    }
+  }
+  phases
+}
+
+/// The state of a transfer processed by the interrupt handler
+pub(crate) struct IrqTransfer {
+  addr: Address,
+  // the positions of the lower address bytes of a 10 bit address within all bytes written
+  prefixes: Vec<usize>,
+  phases: Vec<Phase>,
+  current: usize,
+  // the bytes of the current phase already passed to or taken from the FiFo
+  pos: usize,
+  // whether the phase following the current one has already been started
+  armed: bool,
+  // the offset and length of the last write phase within all bytes written by the transfer
+  last_write: Option<(usize, usize)>,
+  written: usize,
+  // the interrupts enabled when starting a phase
+  interrupts: u32,
+  result: Option<I2cResult<()>>,
+}
+
+impl IrqTransfer {
+  /// Setup the controller for the transfer and start it. With ``interrupts`` enabled the controller raises its
+  /// interrupt once the transfer requires servicing, otherwise the transfer need to be serviced by polling.
+  pub(crate) fn start<B: BscBackend>(
+    bsc: &B,
+    addr: Address,
+    phases: Vec<Phase>,
+    interrupts: bool,
+  ) -> I2cResult<Self> {
+    addr.validate()?;
+    let mut phases: Vec<Phase> = phases
+      .into_iter()
+      .filter(|phase| !phase.data.is_empty())
+      .collect();
+    if phases.is_empty() {
+      // a transfer without any data just addresses the device (quick command)
+      phases.push(Phase::write(Vec::new()));
+    }
+    let mut prefixes = Vec::new();
+    if let Some(low) = addr.low_byte() {
+      // each write to a 10 bit device starts with the lower address bits and a read needs to follow such a write
+      if phases[0].read {
+        phases.insert(0, Phase::write(Vec::new()));
+      }
+      let mut written = 0;
+      for phase in phases.iter_mut().filter(|phase| !phase.read) {
+        phase.data.insert(0, low);
+        prefixes.push(written);
+        written += phase.data.len();
+      }
+    }
+    if let Some(phase) = phases.iter().find(|phase| phase.data.len() > I2C_MAX_DLEN) {
+      return Err(I2cError::InvalidLength {
+        len: phase.data.len(),
+      });
+    }
+    let mut transfer = IrqTransfer {
+      addr,
+      prefixes,
+      last_write: None,
+      phases,
+      current: 0,
+      pos: 0,
+      armed: false,
+      written: 0,
+      interrupts: if interrupts { IRQ_ALL } else { 0 },
+      result: None,
+    };
+    transfer.enter_phase();
+
+    interface::clear_status(bsc);
+    bsc.write(BscRegister::Control, control::FIFO_CLR);
+    bsc.write(BscRegister::SlaveAddress, addr.header() as u32);
+    // pre-fill the FiFo with the first bytes to write
+    transfer.transfer_fifo(bsc);
+    transfer.start_phase(bsc, 0);
+
+    if transfer.phases.len() > 1 {
+      // the next phase is armed as soon as the first one is active on the bus
+      for _ in 0..I2C_ARM_WAIT {
+        let state = bsc.read(BscRegister::Status);
+        if state & (status::TRANS_ACTIVE | status::TRANS_DONE) != 0 {
+          transfer.progress(bsc, state, false);
+          break;
+        }
+      }
+    }
+    Ok(transfer)
+  }
+
+  /// Service the controller FIFO and evaluate the transfer once it has finished
+  pub(crate) fn service<B: BscBackend>(&mut self, bsc: &B) {
+    if self.result.is_some() {
+      return;
+    }
+    let state = bsc.read(BscRegister::Status);
+    self.transfer_fifo(bsc);
+
+    let finished = state & (status::CLK_TIMEOUT | status::TRANS_DONE) != 0;
+    if finished {
+      // the last bytes of a read are still in the FiFo once the transfer is done
+      self.transfer_fifo(bsc);
+      let result = interface::transfer_result(bsc, self.addr.header(), self.last_write, state)
+        .map_err(|error| self.addr.map_error(&self.prefixes, error));
+      interface::clear_status(bsc);
+      if result.is_err() {
+        self.finish(bsc, result);
+        return;
+      }
+    }
+
+    self.progress(bsc, state, finished);
+  }
+
+  /// Continue with the next phase once all bytes of the current phase are processed and the next phase has been
+  /// started
+  fn progress<B: BscBackend>(&mut self, bsc: &B, state: u32, mut finished: bool) {
+    loop {
+      if self.pos < self.phases[self.current].data.len() {
+        self.arm_next(bsc, state);
+        return;
+      }
+      if self.current + 1 == self.phases.len() {
+        // all phases processed
+        if finished {
+          self.finish(bsc, Ok(()));
+        }
+        return;
+      }
+      if !self.armed && finished {
+        // the current phase has already been finished, so the next one is a transfer on its own
+        self.start_phase(bsc, self.current + 1);
+        self.armed = true;
+        // the done state belongs to the previous phase
+        finished = false;
+      }
+      self.arm_next(bsc, state);
+      if !self.armed {
+        return;
+      }
+      self.current += 1;
+      self.pos = 0;
+      self.armed = false;
+      self.enter_phase();
+      self.transfer_fifo(bsc);
+    }
+  }
+
+  /// The time the transfer may take, each phase starts with the address of the device
+  pub(crate) fn timeout(&self, timing: &Timing) -> Duration {
+    let bytes = self.phases.iter().map(|phase| phase.data.len()).sum();
+    timing.timeout(bytes, self.phases.len())
+  }
+
+  /// Whether the transfer has been finished
+  pub(crate) fn is_done(&self) -> bool {
+    self.result.is_some()
+  }
+
+  /// Enable the interrupts of the controller again after they have been masked while the transfer is in progress
+  pub(crate) fn resume<B: BscBackend>(&self, bsc: &B) {
+    if !self.is_done() && self.interrupts != 0 {
+      unmask_interrupts(bsc);
+    }
+  }
+
+  /// Take the result of the finished transfer containing the data read from the device by all read phases
+  pub(crate) fn take_result(&mut self) -> Option<I2cResult<Vec<u8>>> {
+    let result = self.result.take()?;
+    let phases = core::mem::take(&mut self.phases);
+    Some(result.map(|_| {
+      phases
+        .into_iter()
+        .filter(|phase| phase.read)
+        .flat_map(|phase| phase.data)
+        .collect()
+    }))
+  }
+
+  /// Remember the position of the current phase within all bytes written if this is a write
+  fn enter_phase(&mut self) {
+    let phase = &self.phases[self.current];
+    if !phase.read {
+      self.last_write = Some((self.written, phase.data.len()));
+      self.written += phase.data.len();
+    }
+  }
+
+  /// Start the next phase while the current one is still active, so the controller continues with a repeated START
+  /// condition once the current phase is finished. The controller supports this for a read following a write only,
+  /// which is armed once all bytes of the write are passed to the FiFo. Any other phase is started once the current
+  /// one is done.
+  fn arm_next<B: BscBackend>(&mut self, bsc: &B, state: u32) {
+    let phase = &self.phases[self.current];
+    if !self.armed
+      && self
+        .phases
+        .get(self.current + 1)
+        .is_some_and(|next| next.read)
+      && state & (status::CLK_TIMEOUT | status::TRANS_DONE) == 0
+      && state & status::TRANS_ACTIVE != 0
+      && !phase.read
+      && self.pos == phase.data.len()
+    {
+      self.start_phase(bsc, self.current + 1);
+      self.armed = true;
+    }
+  }
+
+  /// Setup the data length and start the phase with the interrupts of the transfer enabled
+  fn start_phase<B: BscBackend>(&self, bsc: &B, index: usize) {
+    let phase = &self.phases[index];
+    let direction = if phase.read { control::READ } else { 0 };
+    bsc.write(BscRegister::DataLength, phase.data.len() as u32);
+    bsc.write(
+      BscRegister::Control,
+      control::ENABLE | control::STARTTRANS | self.interrupts | direction,
+    );
+  }
+
+  /// Write the bytes of a write phase to the FiFo while it is able to accept data or read the bytes of a read phase
+  /// from the FiFo while it contains data
+  fn transfer_fifo<B: BscBackend>(&mut self, bsc: &B) {
+    let phase = &mut self.phases[self.current];
+    if phase.read {
+      while self.pos < phase.data.len() && bsc.read(BscRegister::Status) & status::RX_DATA != 0 {
+        phase.data[self.pos] = (bsc.read(BscRegister::Fifo) & 0xFF) as u8;
+        self.pos += 1;
+      }
+    } else {
+      while self.pos < phase.data.len() && bsc.read(BscRegister::Status) & status::TX_DATA != 0 {
+        bsc.write(BscRegister::Fifo, phase.data[self.pos] as u32);
+        self.pos += 1;
+      }
+    }
+  }
+
+  /// Store the result of the transfer and disable the interrupts of the controller
+  fn finish<B: BscBackend>(&mut self, bsc: &B, result: I2cResult<()>) {
+    bsc.write(BscRegister::Control, control::ENABLE);
+    self.result = Some(result);
+  }
+}
+
+/// Disable the interrupts of the controller without affecting the active transfer
+pub(crate) fn mask_interrupts<B: BscBackend>(bsc: &B) {
+  bsc.write(
+    BscRegister::Control,
+    bsc.read(BscRegister::Control) & !IRQ_ALL,
+  );
+}
+
+/// Enable the interrupts of the controller without affecting the active transfer
+pub(crate) fn unmask_interrupts<B: BscBackend>(bsc: &B) {
+  bsc.write(
+    BscRegister::Control,
+    bsc.read(BscRegister::Control) | IRQ_ALL,
+  );
+}
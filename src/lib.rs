@@ -9,8 +9,9 @@
 
 //! # Raspberry Pi I²C bus interface
 //!
-//! Simple access to the I²C bus available on the Raspberry Pi. When the I²C bus is used this reserves the GPIO pins 2
-//! and 3 for exclusive use by the bus.
+//! Simple access to the I²C bus available on the Raspberry Pi. When the I²C bus is used this reserves the GPIO pins
+//! the BSC controller driving the bus is connected to for exclusive use by the bus. For the default bus these are
+//! the GPIO pins 2 and 3.
 //!
 //! # Usage
 //!
@@ -45,17 +46,220 @@
 //! ```
 //! Once done simple use the funtions to write to or read from the device registers as required.
 //!
+//! # Bus frequency
+//!
+//! Besides the standard and fast mode selected with [I2cImpl::initialize] the bus could run at any frequency the
+//! clock divisor of the BSC controller is able to derive from the core clock. The actual frequency is reported back
+//! and could be changed at runtime:
+//! ```no_run
+//! # use ruspiro_i2c::*;
+//! # fn doc() {
+//!     I2C.with_mut(|i2c| {
+//!         let frequency = i2c.initialize_with_config(I2cConfig::new(250_000_000, 50_000)).unwrap();
+//!         println!("I2C bus running at {} Hz", frequency);
+//!         i2c.set_frequency(FAST_MODE_PLUS).unwrap();
+//!     });
+//! # }
+//! ```
+//!
+//! A blocking transfer fails with [I2cError::Timeout] if it takes longer than the bus frequency, the transfer
+//! length and the clock stretch timeout allow, even if the device stretches each clock cycle. A fixed timeout could
+//! be set with [I2cImpl::set_timeout] or for single transfers with [I2cImpl::with_timeout]. A device reset in the
+//! middle of a transfer may keep the SDA line low and block the bus this way. [I2cImpl::recover_bus] clocks the bus
+//! until the device releases SDA and could be run automatically once a transfer timed out by enabling
+//! [I2cImpl::set_auto_recover].
+//!
+//! The configuration also covers the time a device is allowed to stretch the clock before the transfer fails with
+//! [I2cError::ClockStretchTimeout] and the delays of the SDA line after the edges of the SCL line. Both are checked
+//! against the clock divisor derived from the bus frequency.
+//!
+//! # Multiple I²C busses
+//!
+//! Each BSC controller of the Raspberry Pi drives its own I²C bus. Besides the default [I2C] accessor each controller
+//! is available with its own static accessor, e.g. [I2C0] and [I2C1], or as owned handle created with
+//! [I2cImpl::new]. So several busses can be used side by side:
+//! ```no_run
+//! # use ruspiro_i2c::*;
+//! # fn doc() {
+//!     I2C0.with_mut(|i2c| i2c.initialize(250_000_000, false)).unwrap();
+//!     I2C1.with_mut(|i2c| i2c.initialize(250_000_000, true)).unwrap();
+//!     let sensor = I2C0.with_mut(|i2c| i2c.read_register_u8(0x68, 0x20)).unwrap();
+//!     I2C1.with_mut(|i2c| i2c.write_register_u8(0x3C, 0x00, sensor)).unwrap();
+//! # }
+//! ```
+//!
+//! Additional busses on arbitrary GPIO pins are driven in software by a [SoftI2c] using the [backend::SoftBsc]. It
+//! provides the same API, including clock stretching and repeated START conditions:
+//! ```no_run
+//! # use ruspiro_i2c::*;
+//! # use ruspiro_i2c::backend::SoftBsc;
+//! # fn doc() {
+//!     let mut i2c = SoftI2c::with_backend(SoftBsc::new(17, 27, 250_000_000).unwrap());
+//!     i2c.initialize(250_000_000, false).unwrap();
+//!     let devices = i2c.scan().unwrap();
+//! # }
+//! ```
+//!
+//! # embedded-hal
+//!
+//! The [I2cImpl] implements the ``embedded_hal::i2c::I2c`` trait of ``embedded-hal`` 1.0. So device drivers
+//! written against this trait work on top of this I²C bus directly. Consecutive operations of a transaction of the
+//! same kind are merged. A read following a write is joined with a repeated START condition. The BSC controller does
+//! not support a repeated START for any other operation order, so a transaction with a write following a read fails
+//! with [I2cError::UnsupportedTransaction].
+//!
+//! With the ``embedded-hal-02`` feature the ``embedded-hal`` 0.2 blocking I²C traits are implemented for a shared
+//! reference to the [I2cImpl]. Each driver gets a copy of this reference, so drivers using the older and the newer
+//! traits could be mixed on the same bus:
+//! ```ignore
+//! # use ruspiro_i2c::*;
+//! # fn doc() {
+//!     I2C.with_ref(|i2c| {
+//!         let mut sensor = OldSensorDriver::new(i2c);
+//!         let mut display = OtherOldDriver::new(i2c);
+//!     });
+//! # }
+//! ```
+//!
+//! # Interrupt driven transfers
+//!
+//! Besides the blocking functions a transfer could be started with [I2cImpl::start_write], [I2cImpl::start_read] or
+//! [I2cImpl::start_write_read]. Those return immediately and the FIFO of the controller is serviced from the
+//! interrupt handler. The interrupt manager need to call [handle_i2c_interrupt] for the I²C interrupt line. Once the
+//! transfer has been finished its result is available with [I2cImpl::transfer_result]. While the transfer is in
+//! progress any other function using the bus fails with [I2cError::Busy], the next transfer could not be started
+//! before the result of the previous one has been taken. The interrupt handler does not lock the static I²C bus
+//! accessors, so the bus could be accessed within ``with_mut`` while the transfer is in progress.
+//!
+//! # Asynchronous transfers
+//!
+//! For use with an async executor [I2cImpl::read_register_buff_async], [I2cImpl::write_register_buff_async] and
+//! [I2cImpl::transaction_async] return futures completing once the controller has finished the transfer. The
+//! ``embedded_hal_async::i2c::I2c`` trait is implemented on top of them. By default the task is woken immediately to
+//! poll the controller again. With [I2cImpl::set_interrupt_wake] the task is woken from the interrupt of the BSC
+//! controller instead:
+//! ```no_run
+//! # use ruspiro_i2c::*;
+//! # async fn doc() {
+//!     let mut i2c = I2cImpl::new(Bsc::Bsc1);
+//!     i2c.initialize(250_000_000, true).unwrap();
+//!     i2c.set_interrupt_wake(true);
+//!     let mut buffer: [u8; 4] = [0; 4];
+//!     i2c.read_register_buff_async(0x68, 0x20, &mut buffer).await.unwrap();
+//! # }
+//! ```
+//!
+//! Dropping the future before it has completed aborts the transfer, so the bus could be used right away:
+//! ```
+//! # use ruspiro_i2c::I2cImpl;
+//! # use ruspiro_i2c::backend::sim::*;
+//! # use ruspiro_i2c::backend::{status, BscBackend, BscRegister};
+//! # use core::future::Future;
+//! # use core::task::{Context, RawWaker, RawWakerVTable, Waker};
+//! # fn raw() -> RawWaker {
+//! #     RawWaker::new(core::ptr::null(), &VTABLE)
+//! # }
+//! # static VTABLE: RawWakerVTable = RawWakerVTable::new(|_| raw(), |_| (), |_| (), |_| ());
+//! # let waker = unsafe { Waker::from_raw(raw()) };
+//! let device = SimRegisterDevice::new(256);
+//! let recorder = SimRecorder::new(device.clone());
+//! let bsc = SimulatedBsc::new();
+//! bsc.attach(0x68, recorder.clone());
+//! let mut i2c = I2cImpl::with_backend(bsc);
+//! i2c.initialize(250_000_000, true).unwrap();
+//!
+//! {
+//!     let mut future = Box::pin(i2c.write_register_buff_async(0x68, 0x00, &[0x5A; 64]));
+//!     assert!(future.as_mut().poll(&mut Context::from_waker(&waker)).is_pending());
+//! }
+//! assert_eq!(i2c.backend().read(BscRegister::Status) & status::TRANS_ACTIVE, 0);
+//!
+//! recorder.clear();
+//! i2c.write_register_u8(0x68, 0x20, 0x01).unwrap();
+//! assert_eq!(device.register(0x20), 0x01);
+//! assert_eq!(
+//!     recorder.events(),
+//!     [
+//!         SimEvent::Start { read: false },
+//!         SimEvent::Write(0x20),
+//!         SimEvent::Write(0x01),
+//!         SimEvent::Stop,
+//!     ]
+//! );
+//! ```
+//!
+//! Like the blocking transfers the asynchronous ones fail with [I2cError::Timeout] once they take longer than the
+//! timeout of the bus. The timeout is checked each time the task is polled, so with the interrupt wake enabled the
+//! clock stretch timeout of the controller should be kept enabled to raise the interrupt for a device holding the
+//! clock line.
+//!
+//! # SMBus
+//!
+//! SMBus devices like smart batteries or power supplies are accessed with the SMBus protocols provided by
+//! [I2cImpl::smbus], see the [smbus] module. Power conversion devices speaking PMBus on top of the SMBus are read
+//! and configured in real world units with the [pmbus] module.
+//!
+//! # DMA
+//!
+//! Transfers are not fed through DMA. The DMA controller of the Raspberry Pi paces its transfers with the DREQ
+//! signals of the peripherals, but only the BSC/SPI slave peripheral provides a DREQ (TX 8, RX 9). The BSC master
+//! controllers driving the I²C busses do not, so an unpaced DMA channel would overrun or underrun the 16 byte FIFO.
+//! Bulk transfers, e.g. streaming a display frame buffer, should use the interrupt driven or asynchronous transfers
+//! instead, which leave the core free while the FIFO is drained by the bus.
+//!
+//! # Simulation
+//!
+//! The controller registers are accessed through a [backend::BscBackend]. Besides the memory mapped hardware this
+//! crate provides the [backend::sim::SimulatedBsc] that models the BSC controller in software. Attaching simulated
+//! slave devices to it allows to run the I²C bus on any host, e.g. within unit tests of a device driver.
+//!
 //! # Features
 //!
 //! - ``ruspiro_pi3`` is active by default and ensures the proper MMIO base address is used for Raspberry Pi 3
+//! - ``ruspiro_pi4`` ensures the proper MMIO base address is used for Raspberry Pi 4 and enables the additional BSC
+//!   controllers BSC3 to BSC6 of this model. It excludes ``ruspiro_pi3``, so the default features need to be
+//!   disabled.
+//! - ``embedded-hal-02`` implements the ``embedded-hal`` 0.2 blocking I²C traits
 //!
 
 extern crate alloc;
-use alloc::vec::Vec;
+use alloc::{vec, vec::Vec};
+use core::time::Duration;
+use embedded_hal::i2c::Operation;
 use ruspiro_mmio_register::*;
 use ruspiro_singleton::Singleton;
 
+mod address;
+pub use address::{Address, Reserved, SevenBitAddress};
+pub mod backend;
+use backend::{BscBackend, MmioBackend, SoftBsc};
+
+mod config;
+pub use config::{
+  ClockStretchTimeout, DataDelay, I2cConfig, FAST_MODE, FAST_MODE_PLUS, STANDARD_MODE,
+};
+
+mod controller;
+pub use controller::{AltFunction, Bsc, BscPins};
+
+mod asynch;
+mod device_id;
+pub use device_id::{DeviceId, MANUFACTURERS};
+mod error;
+pub use error::I2cError;
+mod hal;
+#[cfg(feature = "embedded-hal-02")]
+mod hal02;
 mod interface;
+mod irq;
+pub use irq::handle_i2c_interrupt;
+pub mod pmbus;
+mod scan;
+pub use scan::{Probe, ScanConfig, ScanMode};
+pub mod smbus;
+
+const GENERAL_CALL_RESET: u8 = 0x06; // general call resetting the devices and latching their programmable address
+const GENERAL_CALL_LATCH: u8 = 0x04; // general call latching the programmable address without a reset
 
 /// Static singleton accessor for the I²C bus peripheral
 /// To use the contained i2c API in a safe way use the ``with_mut``
@@ -69,39 +273,295 @@ mod interface;
 /// });
 /// # }
 /// ```
-pub static I2C: Singleton<I2cImpl> = Singleton::new(I2cImpl::new());
+/// This is the I²C bus driven by the BSC1 controller available at the GPIO pins 2 and 3 and the same as [I2C1].
+pub use crate::I2C1 as I2C;
+
+/// Static singleton accessor for the I²C bus driven by the BSC0 controller
+pub static I2C0: Singleton<I2cImpl> = Singleton::new(I2cImpl::new(Bsc::Bsc0));
+
+/// Static singleton accessor for the I²C bus driven by the BSC1 controller
+pub static I2C1: Singleton<I2cImpl> = Singleton::new(I2cImpl::new(Bsc::Bsc1));
+
+/// Static singleton accessor for the I²C bus driven by the BSC3 controller
+#[cfg(feature = "ruspiro_pi4")]
+pub static I2C3: Singleton<I2cImpl> = Singleton::new(I2cImpl::new(Bsc::Bsc3));
+
+/// Static singleton accessor for the I²C bus driven by the BSC4 controller
+#[cfg(feature = "ruspiro_pi4")]
+pub static I2C4: Singleton<I2cImpl> = Singleton::new(I2cImpl::new(Bsc::Bsc4));
+
+/// Static singleton accessor for the I²C bus driven by the BSC5 controller
+#[cfg(feature = "ruspiro_pi4")]
+pub static I2C5: Singleton<I2cImpl> = Singleton::new(I2cImpl::new(Bsc::Bsc5));
+
+/// Static singleton accessor for the I²C bus driven by the BSC6 controller
+#[cfg(feature = "ruspiro_pi4")]
+pub static I2C6: Singleton<I2cImpl> = Singleton::new(I2cImpl::new(Bsc::Bsc6));
 
-/// I²C peripheral representation
-pub struct I2cImpl {
+/// I²C peripheral representation. The controller registers are accessed through the backend ``B``, which is the
+/// memory mapped hardware controller by default.
+pub struct I2cImpl<B = MmioBackend> {
+  backend: B,
   initialized: bool,
+  config: I2cConfig,
+  timeout: Option<Duration>,
+  auto_recover: bool,
+  // the interrupt driven transfer of a backend not serviced by handle_i2c_interrupt
+  irq: irq::IrqSlot,
+  irq_wake: Option<Bsc>,
 }
 
-pub type I2cResult<T> = Result<T, &'static str>;
+/// The result of any I²C bus operation
+pub type I2cResult<T> = Result<T, I2cError>;
+
+/// An I²C bus driven in software on arbitrary GPIO pins, see [SoftBsc]
+pub type SoftI2c = I2cImpl<SoftBsc>;
 
 impl I2cImpl {
-  /// create a new instance of the I2c implementation. This will only be used to
-  /// prepare the static singleton I²C accessor.
-  pub(crate) const fn new() -> Self {
-    I2cImpl { initialized: false }
+  /// Create a new instance of the I2c implementation driving the given BSC controller. This is an owned handle to the
+  /// controller as an alternative to the static singleton accessors. Only one handle is able to initialize a specific
+  /// controller as the initialization reserves the GPIO pins of this controller.
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # fn doc() {
+  ///     let mut i2c = I2cImpl::new(Bsc::Bsc0);
+  ///     i2c.initialize(250_000_000, true).unwrap();
+  /// # }
+  /// ```
+  pub const fn new(bsc: Bsc) -> Self {
+    I2cImpl::with_backend(MmioBackend::new(bsc))
   }
 
-  /// Initialize the I²C bus for further usage. This will require the GPIO pins 2 and 3 to be available for usage.
-  /// If they have been already occupied before this initialization is called an error will be returned.
+  /// The BSC controller driven by this I²C bus
+  pub fn bsc(&self) -> Bsc {
+    self.backend.bsc()
+  }
+
+  /// Enable or disable waking the tasks awaiting an asynchronous transfer from the interrupt of the BSC controller.
+  /// If enabled the interrupt manager need to call [handle_i2c_interrupt] for the I²C interrupt line, otherwise the
+  /// task is woken immediately to poll the controller again.
+  pub fn set_interrupt_wake(&mut self, enabled: bool) {
+    self.irq_wake = if enabled { Some(self.bsc()) } else { None };
+  }
+}
+
+impl<B> I2cImpl<B> {
+  /// Create a new instance of the I2c implementation driving the controller through the given backend. This is
+  /// typically used to run the I²C bus against a [backend::sim::SimulatedBsc].
+  pub const fn with_backend(backend: B) -> Self {
+    I2cImpl {
+      backend,
+      initialized: false,
+      config: I2cConfig::new(0, STANDARD_MODE),
+      timeout: None,
+      auto_recover: false,
+      irq: irq::IrqSlot::new(),
+      irq_wake: None,
+    }
+  }
+
+  /// Access the backend the controller registers are accessed with
+  pub fn backend(&self) -> &B {
+    &self.backend
+  }
+}
+
+impl<B: BscBackend> I2cImpl<B> {
+  /// Initialize the I²C bus for further usage. This will require the GPIO pins of the BSC controller to be available
+  /// for usage. If they have been already occupied before this initialization is called an error will be returned.
+  /// The bus runs in fast mode (400 kHz) or in standard mode (100 kHz). Use [I2cImpl::initialize_with_config] to
+  /// run the bus at any other frequency.
   pub fn initialize(&mut self, core_speed: u32, fast_mode: bool) -> I2cResult<()> {
+    let frequency = if fast_mode { FAST_MODE } else { STANDARD_MODE };
+    self
+      .initialize_with_config(I2cConfig::new(core_speed, frequency))
+      .map(|_| ())
+  }
+
+  /// Initialize the I²C bus with the given configuration. Returns the actual bus frequency, which could be lower
+  /// than the requested one as the clock divisor of the controller is an even integer. A frequency that could not be
+  /// achieved at all is rejected before the GPIO pins are claimed. If the bus is already initialized the
+  /// configuration is not applied, use [I2cImpl::set_frequency] to change the bus frequency instead.
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # fn doc() {
+  ///     let config = I2cConfig::new(250_000_000, FAST_MODE_PLUS);
+  ///     let frequency = I2C.with_mut(|i2c| i2c.initialize_with_config(config)).unwrap();
+  ///     println!("I2C bus running at {} Hz", frequency);
+  /// # }
+  /// ```
+  pub fn initialize_with_config(&mut self, config: I2cConfig) -> I2cResult<u32> {
     if !self.initialized {
-      interface::initialize(core_speed, fast_mode).and_then(|_| {
-        self.initialized = true;
-        Ok(())
-      })
+      let frequency = interface::initialize(&self.backend, &config)?;
+      self.config = config;
+      self.initialized = true;
+      Ok(frequency)
     } else {
-      Ok(())
+      self.config.actual_frequency()
     }
   }
 
+  /// Apply the configuration to the initialized I²C bus without claiming the GPIO pins again. Nothing is changed if
+  /// any part of the configuration is invalid. Returns the actual bus frequency.
+  pub fn configure(&mut self, config: I2cConfig) -> I2cResult<u32> {
+    self.is_initializied()?;
+    let frequency = interface::configure(&self.backend, &config)?;
+    self.config = config;
+    Ok(frequency)
+  }
+
+  /// Change the frequency of the initialized I²C bus without claiming the GPIO pins again. A clock stretch timeout
+  /// given in microseconds is adjusted to the new frequency. Returns the actual bus frequency.
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # fn doc() {
+  ///     // slow down the bus for a device at the end of a long cable
+  ///     let frequency = I2C.with_mut(|i2c| i2c.set_frequency(10_000)).unwrap();
+  /// # }
+  /// ```
+  pub fn set_frequency(&mut self, frequency: u32) -> I2cResult<u32> {
+    self.configure(I2cConfig {
+      frequency,
+      ..self.config
+    })
+  }
+
+  /// Change the time a device is allowed to stretch the clock of the initialized I²C bus
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # fn doc() {
+  ///     I2C.with_mut(|i2c| i2c.set_clock_stretch_timeout(ClockStretchTimeout::Cycles(1000))).unwrap();
+  /// # }
+  /// ```
+  pub fn set_clock_stretch_timeout(&mut self, timeout: ClockStretchTimeout) -> I2cResult<()> {
+    self
+      .configure(self.config.with_clock_stretch_timeout(timeout))
+      .map(|_| ())
+  }
+
+  /// Change the delays of the SDA line after the edges of the SCL line of the initialized I²C bus
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # fn doc() {
+  ///     I2C.with_mut(|i2c| i2c.set_data_delay(DataDelay::new(0x60, 0x60))).unwrap();
+  /// # }
+  /// ```
+  pub fn set_data_delay(&mut self, delay: DataDelay) -> I2cResult<()> {
+    self
+      .configure(self.config.with_data_delay(delay))
+      .map(|_| ())
+  }
+
+  /// The configuration the I²C bus is running with
+  pub fn config(&self) -> I2cConfig {
+    self.config
+  }
+
+  /// Set the time a blocking transfer may take before it fails with [I2cError::Timeout]. If no timeout is given it's
+  /// calculated from the bus frequency and the length of each transfer, which is the default.
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # use core::time::Duration;
+  /// # fn doc() {
+  ///     I2C.with_mut(|i2c| i2c.set_timeout(Some(Duration::from_millis(50))));
+  /// # }
+  /// ```
+  pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+    self.timeout = timeout;
+  }
+
+  /// The time a blocking transfer may take, if set
+  pub fn timeout(&self) -> Option<Duration> {
+    self.timeout
+  }
+
+  /// Run the closure with the given timeout applied to the transfers issued within. The previous timeout is restored
+  /// afterwards.
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # use core::time::Duration;
+  /// # fn doc() {
+  ///     // the device needs some time to finish the conversion before acknowledging the read
+  ///     let value = I2C.with_mut(|i2c| {
+  ///         i2c.with_timeout(Duration::from_millis(100), |i2c| i2c.read_register_u8(0x40, 0xE3))
+  ///     });
+  /// # }
+  /// ```
+  pub fn with_timeout<F, R>(&mut self, timeout: Duration, f: F) -> R
+  where
+    F: FnOnce(&mut Self) -> R,
+  {
+    let previous = self.timeout.replace(timeout);
+    let result = f(self);
+    self.timeout = previous;
+    result
+  }
+
+  /// Release a device holding the SDA line low, e.g. after it has been reset in the middle of a transfer. The GPIO
+  /// pins of the bus are temporarily taken from the BSC controller to clock SCL up to nine times until the device
+  /// releases SDA, followed by a STOP condition. If SDA is still held low afterwards [I2cError::BusStuck] is returned.
+  /// The [SoftBsc](backend::SoftBsc) clocks its own bus lines instead, while a backend not driving any bus lines
+  /// fails with [I2cError::RecoveryUnsupported].
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # fn doc() {
+  ///     I2C.with_mut(|i2c| {
+  ///         if let Err(I2cError::Timeout { .. }) = i2c.read_register_u8(0x68, 0x20) {
+  ///             i2c.recover_bus().unwrap();
+  ///         }
+  ///     });
+  /// # }
+  /// ```
+  ///
+  /// The simulated controller has no bus lines to recover:
+  /// ```
+  /// # use ruspiro_i2c::{I2cError, I2cImpl};
+  /// # use ruspiro_i2c::backend::sim::SimulatedBsc;
+  /// let mut i2c = I2cImpl::with_backend(SimulatedBsc::new());
+  /// i2c.initialize(250_000_000, true).unwrap();
+  /// assert_eq!(i2c.recover_bus(), Err(I2cError::RecoveryUnsupported));
+  /// ```
+  pub fn recover_bus(&self) -> I2cResult<()> {
+    self.is_initializied()?;
+    interface::recover_bus(&self.backend, &self.timing())
+  }
+
+  /// Enable or disable the automatic bus recovery once a blocking transfer has timed out. The transfer still fails
+  /// with [I2cError::Timeout], but the following transfers are able to use the bus again.
+  pub fn set_auto_recover(&mut self, enabled: bool) {
+    self.auto_recover = enabled;
+  }
+
+  /// The timing the timeout of a blocking transfer is derived from
+  pub(crate) fn timing(&self) -> interface::Timing {
+    interface::Timing {
+      timeout: self.timeout,
+      frequency: self.config.actual_frequency().unwrap_or(STANDARD_MODE),
+      stretch: self.config.timeout_cycles().unwrap_or(0),
+      recover: self.auto_recover,
+    }
+  }
+
+  /// The actual frequency the I²C bus is running at
+  pub fn frequency(&self) -> I2cResult<u32> {
+    self.is_initializied()?;
+    self.config.actual_frequency()
+  }
+
   /// Scan for I²C devices currently connected to the I²C bus.
   /// The scan will just try to get an acknowledge message from any slave address between
-  /// 0x00 and 0x7F. If a device is connected this call succeeds/get's acknowledged and the
-  /// corresponding address is put into the returned vector.
+  /// 0x08 and 0x77. If a device is connected this call succeeds/get's acknowledged and the
+  /// corresponding address is put into the returned vector. The reserved addresses are not probed,
+  /// see [SevenBitAddress::is_reserved]. Each address is probed as configured by [ScanConfig::new],
+  /// use [I2cImpl::scan_with] to probe in a different way.
   /// # Example
   /// ```no_run
   /// # use ruspiro_i2c::*;
@@ -111,12 +571,63 @@ impl I2cImpl {
   ///         println!("Device at address: 0x{:X}", d);
   ///     }
   /// # }
+  /// ```
   pub fn scan(&self) -> I2cResult<Vec<u8>> {
+    Ok(
+      self
+        .scan_with(&ScanConfig::new())?
+        .into_iter()
+        .filter(|&(_, probe)| probe == Probe::Acknowledged)
+        .map(|(addr, _)| addr.value())
+        .collect(),
+    )
+  }
+
+  /// Scan for I²C devices with the given configuration and return the outcome of the probe of each address
+  /// scanned. A device that did not respond in time is reported as [Probe::Busy] instead of failing the scan.
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # fn doc() {
+  ///     // probe the EEPROM addresses with a read only
+  ///     let config = ScanConfig::new()
+  ///         .with_mode(ScanMode::Read)
+  ///         .with_range(0x50, 0x57);
+  ///     let result = I2C.with_mut(|i2c| i2c.scan_with(&config)).unwrap();
+  ///     for (addr, probe) in result {
+  ///         println!("{}: {:?}", addr, probe);
+  ///     }
+  /// # }
+  /// ```
+  pub fn scan_with(&self, config: &ScanConfig) -> I2cResult<Vec<(SevenBitAddress, Probe)>> {
+    self.is_initializied()?;
+    interface::scan_devices(&self.backend, &self.timing(), config)
+  }
+
+  /// Scan for devices with a 10 bit address between 0x000 and 0x3FF. Each address is probed by writing it to the
+  /// bus and reading one byte after a repeated START condition, so this takes a while and is therefore not part of
+  /// [I2cImpl::scan].
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # fn doc() {
+  ///     let devices = I2C.with_mut(|i2c| i2c.scan_ten_bit()).unwrap();
+  ///     for d in devices {
+  ///         println!("Device at 10 bit address: 0x{:03X}", d);
+  ///     }
+  /// # }
+  /// ```
+  pub fn scan_ten_bit(&self) -> I2cResult<Vec<u16>> {
     self.is_initializied()?;
-    Ok(interface::scan_devices())
+    Ok(interface::scan_ten_bit_devices(
+      &self.backend,
+      &self.timing(),
+    ))
   }
 
-  /// Checks if a device with the given address is connected to the I²C bus.
+  /// Checks if a device with the given address is connected to the I²C bus. Like all functions addressing a device
+  /// this accepts a 7 bit address given as `u8` or [SevenBitAddress] or an explicit [Address], which could be a 10
+  /// bit address as well. A `u8` exceeding the 7 bit range fails with [I2cError::InvalidAddress].
   /// # Example
   /// ```no_run
   /// # use ruspiro_i2c::*;
@@ -124,11 +635,181 @@ impl I2cImpl {
   ///     if I2C.with_mut(|i2c| i2c.check_device(0x68)).is_ok() {
   ///         println!("device at 0x68 connected");
   ///     }
+  ///     // the address given in 8 bit notation in the datasheet
+  ///     let addr = SevenBitAddress::from_shifted(0xD0);
+  ///     if I2C.with_mut(|i2c| i2c.check_device(addr)).is_ok() {
+  ///         println!("device at {} connected", addr);
+  ///     }
+  ///     if I2C.with_mut(|i2c| i2c.check_device(Address::TenBit(0x2A5))).is_ok() {
+  ///         println!("device at 0x2A5 connected");
+  ///     }
   /// # }
   /// ```
-  pub fn check_device(&self, addr: u8) -> I2cResult<()> {
+  pub fn check_device(&self, addr: impl Into<Address>) -> I2cResult<()> {
     self.is_initializied()?;
-    interface::check_device(addr)
+    let addr = addr.into();
+    // a read from the general call address is the START byte, which no device acknowledges
+    if addr == Address::from(SevenBitAddress::GENERAL_CALL) {
+      return Err(I2cError::InvalidAddress { addr });
+    }
+    interface::check_device(&self.backend, &self.timing(), addr)
+  }
+
+  /// Send the data to all devices on the bus listening to the general call address. The meaning of the first byte
+  /// is defined by the I²C specification if its lowest bit is clear, see [I2cImpl::general_call_reset] and
+  /// [I2cImpl::general_call_latch]. If it is set the general call is a hardware general call and the first byte
+  /// carries the address of the master sending it. Fails with [I2cError::AddressNack] if no device listens to the
+  /// general call.
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # fn doc() {
+  ///     // hardware general call of the master at 0x10 followed by one data byte
+  ///     I2C.with_mut(|i2c| i2c.general_call(&[0x10 << 1 | 1, 0x42])).unwrap();
+  /// # }
+  /// ```
+  ///
+  /// The general calls as seen by a device listening to the general call address:
+  /// ```
+  /// # use ruspiro_i2c::{I2cError, I2cImpl};
+  /// # use ruspiro_i2c::backend::sim::*;
+  /// use SimEvent::{Start, Stop, Write};
+  ///
+  /// let device = SimRecorder::new(SimRegisterDevice::new(256));
+  /// let bsc = SimulatedBsc::new();
+  /// bsc.attach(0x00, device.clone());
+  /// let mut i2c = I2cImpl::with_backend(bsc);
+  /// i2c.initialize(250_000_000, true).unwrap();
+  ///
+  /// i2c.general_call(&[0x10 << 1 | 1, 0x42]).unwrap();
+  /// i2c.general_call_reset().unwrap();
+  /// i2c.general_call_latch().unwrap();
+  /// assert_eq!(
+  ///     device.events(),
+  ///     vec![
+  ///         Start { read: false },
+  ///         Write(0x21),
+  ///         Write(0x42),
+  ///         Stop,
+  ///         Start { read: false },
+  ///         Write(0x06),
+  ///         Stop,
+  ///         Start { read: false },
+  ///         Write(0x04),
+  ///         Stop,
+  ///     ]
+  /// );
+  /// assert_eq!(i2c.general_call(&[]), Err(I2cError::InvalidLength { len: 0 }));
+  /// ```
+  pub fn general_call(&self, data: &[u8]) -> I2cResult<()> {
+    self.is_initializied()?;
+    if data.is_empty() {
+      return Err(I2cError::InvalidLength { len: 0 });
+    }
+    interface::write_data(
+      &self.backend,
+      &self.timing(),
+      SevenBitAddress::GENERAL_CALL.into(),
+      data,
+    )
+  }
+
+  /// Reset all devices supporting the general call and let them latch the programmable part of their address
+  pub fn general_call_reset(&self) -> I2cResult<()> {
+    self.general_call(&[GENERAL_CALL_RESET])
+  }
+
+  /// Let all devices supporting the general call latch the programmable part of their address without a reset
+  pub fn general_call_latch(&self) -> I2cResult<()> {
+    self.general_call(&[GENERAL_CALL_LATCH])
+  }
+
+  /// Read the three raw bytes of the Device ID of the device with the given address. The device address is written
+  /// to the reserved Device ID address followed by a repeated START reading the Device ID. Fails with
+  /// [I2cError::AddressNack] if no device on the bus supports the Device ID or with [I2cError::DataNack] if the
+  /// addressed device does not.
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # fn doc() {
+  ///     let id = I2C.with_mut(|i2c| i2c.read_device_id_raw(0x68)).unwrap();
+  /// # }
+  /// ```
+  ///
+  /// The device address is written to the Device ID address 0x7C followed by a repeated START reading the ID:
+  /// ```
+  /// # use ruspiro_i2c::{Address, I2cError, I2cImpl};
+  /// # use ruspiro_i2c::backend::sim::*;
+  /// use SimEvent::{Read, Start, Stop, Write};
+  ///
+  /// // the Device ID of the device at 0x68 starts at the register selected by its shifted address
+  /// let registers = SimRegisterDevice::new(256);
+  /// registers.set_register(0xD0, 0x00);
+  /// registers.set_register(0xD1, 0x0C);
+  /// registers.set_register(0xD2, 0x83);
+  /// let device_id = SimRecorder::new(registers);
+  /// let target = SimRecorder::new(SimRegisterDevice::new(256));
+  /// let bsc = SimulatedBsc::new();
+  /// bsc.attach(0x7C, device_id.clone());
+  /// bsc.attach(0x68, target.clone());
+  /// let mut i2c = I2cImpl::with_backend(bsc);
+  /// i2c.initialize(250_000_000, true).unwrap();
+  ///
+  /// assert_eq!(i2c.read_device_id_raw(0x68).unwrap(), [0x00, 0x0C, 0x83]);
+  /// assert_eq!(
+  ///     device_id.events(),
+  ///     vec![
+  ///         Start { read: false },
+  ///         Write(0x68 << 1),
+  ///         Start { read: true },
+  ///         Read(0x00),
+  ///         Read(0x0C),
+  ///         Read(0x83),
+  ///         Stop,
+  ///     ]
+  /// );
+  /// // the device itself is not addressed
+  /// assert!(target.events().is_empty());
+  ///
+  /// i2c.backend().detach(0x7C);
+  /// assert_eq!(
+  ///     i2c.read_device_id_raw(0x68),
+  ///     Err(I2cError::AddressNack { addr: Address::SevenBit(0x7C) })
+  /// );
+  /// ```
+  pub fn read_device_id_raw(&self, device_addr: impl Into<Address>) -> I2cResult<[u8; 3]> {
+    self.is_initializied()?;
+    let device_addr = device_addr.into();
+    device_addr.validate()?;
+    // the R/W bit following the device address is ignored, a 10 bit address is given by both of its address bytes
+    let request = [
+      device_addr.header() << 1,
+      device_addr.low_byte().unwrap_or(0),
+    ];
+    let len = if device_addr.is_ten_bit() { 2 } else { 1 };
+    let mut id = [0; 3];
+    interface::write_read(
+      &self.backend,
+      &self.timing(),
+      SevenBitAddress::DEVICE_ID.into(),
+      &request[..len],
+      &mut id,
+    )?;
+    Ok(id)
+  }
+
+  /// Read the Device ID of the device with the given address and decode its manufacturer, part identification and
+  /// revision, see [I2cImpl::read_device_id_raw] for the transfer.
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # fn doc() {
+  ///     let id = I2C.with_mut(|i2c| i2c.read_device_id(0x68)).unwrap();
+  ///     println!("{} part 0x{:03X}", id.manufacturer_name().unwrap_or("unknown"), id.part());
+  /// # }
+  /// ```
+  pub fn read_device_id(&self, device_addr: impl Into<Address>) -> I2cResult<DeviceId> {
+    self.read_device_id_raw(device_addr).map(DeviceId::from)
   }
 
   /// Read a u8 from a device register
@@ -139,9 +820,9 @@ impl I2cImpl {
   ///     let value = I2C.with_mut(|i2c| i2c.read_register_u8(0x68, 0x20)).unwrap();
   /// # }
   /// ```
-  pub fn read_register_u8(&self, device_addr: u8, reg: u8) -> I2cResult<u8> {
+  pub fn read_register_u8(&self, device_addr: impl Into<Address>, reg: u8) -> I2cResult<u8> {
     self.is_initializied()?;
-    interface::read_reg_u8(device_addr, reg)
+    interface::read_reg_u8(&self.backend, &self.timing(), device_addr.into(), reg)
   }
 
   /// Read a u16 from a device register.
@@ -156,14 +837,16 @@ impl I2cImpl {
   ///     let value = I2C.with_mut(|i2c| i2c.read_register_u16(0x68, 0x20)).unwrap();
   /// # }
   /// ```
-  pub fn read_register_u16(&self, device_addr: u8, reg: u8) -> I2cResult<u16> {
+  pub fn read_register_u16(&self, device_addr: impl Into<Address>, reg: u8) -> I2cResult<u16> {
     self.is_initializied()?;
-    interface::read_reg_u16(device_addr, reg)
+    interface::read_reg_u16(&self.backend, &self.timing(), device_addr.into(), reg)
   }
 
   /// Read a u8 array from a device register.
   /// As usually all I²C register are 8 Bit wide this will only return a valid value
   /// if the device supports auto-increment of the actual register while reading
+  /// The buffer is not limited by the FIFO size of the controller. Buffers exceeding 65535 bytes are transferred in
+  /// several transfers, each finished with a STOP condition.
   /// # Example
   /// ```no_run
   /// # use ruspiro_i2c::*;
@@ -177,12 +860,73 @@ impl I2cImpl {
   /// ```
   pub fn read_register_buff(
     &self,
-    device_addr: u8,
+    device_addr: impl Into<Address>,
     reg: u8,
     buffer: &mut [u8],
   ) -> I2cResult<usize> {
     self.is_initializied()?;
-    interface::read_reg_data(device_addr, reg, buffer)
+    interface::read_reg_data(
+      &self.backend,
+      &self.timing(),
+      device_addr.into(),
+      reg,
+      buffer,
+    )
+  }
+
+  /// Write data to a device and read data from it in one combined transfer. The read follows the write with a
+  /// repeated START condition instead of a STOP and START condition, which is required by devices that loose their
+  /// register pointer once the bus is released. The register read functions use this combined transfer already.
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # fn doc() {
+  ///     let mut buffer: [u8; 3] = [0; 3];
+  ///     // read the object temperature from a MLX90614 sensor
+  ///     let _ = I2C.with_mut(|i2c| i2c.write_read(0x5A, &[0x07], &mut buffer)).unwrap();
+  /// # }
+  /// ```
+  ///
+  /// The read follows the write without a STOP condition in between:
+  /// ```
+  /// # use ruspiro_i2c::I2cImpl;
+  /// # use ruspiro_i2c::backend::sim::*;
+  /// let registers = SimRegisterDevice::new(256);
+  /// registers.set_register(0x07, 0x42);
+  /// let device = SimRecorder::new(registers);
+  /// let bsc = SimulatedBsc::new();
+  /// bsc.attach(0x5A, device.clone());
+  /// let mut i2c = I2cImpl::with_backend(bsc);
+  /// i2c.initialize(250_000_000, true).unwrap();
+  ///
+  /// let mut buffer = [0; 2];
+  /// i2c.write_read(0x5A, &[0x07], &mut buffer).unwrap();
+  /// assert_eq!(
+  ///     device.events(),
+  ///     vec![
+  ///         SimEvent::Start { read: false },
+  ///         SimEvent::Write(0x07),
+  ///         SimEvent::Start { read: true },
+  ///         SimEvent::Read(0x42),
+  ///         SimEvent::Read(0x00),
+  ///         SimEvent::Stop,
+  ///     ]
+  /// );
+  /// ```
+  pub fn write_read(
+    &self,
+    device_addr: impl Into<Address>,
+    wbuf: &[u8],
+    rbuf: &mut [u8],
+  ) -> I2cResult<usize> {
+    self.is_initializied()?;
+    interface::write_read(
+      &self.backend,
+      &self.timing(),
+      device_addr.into(),
+      wbuf,
+      rbuf,
+    )
   }
 
   /// Read a specific field from a 8 Bit device register.
@@ -198,12 +942,12 @@ impl I2cImpl {
   /// ```
   pub fn read_register_field(
     &self,
-    device_addr: u8,
+    device_addr: impl Into<Address>,
     reg: u8,
     field: RegisterField<u8>,
   ) -> I2cResult<RegisterFieldValue<u8>> {
     self.is_initializied()?;
-    let value = interface::read_reg_u8(device_addr, reg)?;
+    let value = interface::read_reg_u8(&self.backend, &self.timing(), device_addr.into(), reg)?;
     Ok(RegisterFieldValue::<u8>::new(field, value >> field.shift()))
   }
 
@@ -218,9 +962,9 @@ impl I2cImpl {
   ///     I2C.with_mut(|i2c| i2c.write_u8(0x68, 12)).unwrap();
   /// # }
   /// ```
-  pub fn write_u8(&self, device_addr: u8, data: u8) -> I2cResult<()> {
+  pub fn write_u8(&self, device_addr: impl Into<Address>, data: u8) -> I2cResult<()> {
     self.is_initializied()?;
-    interface::write_raw_u8(device_addr, data)
+    interface::write_raw_u8(&self.backend, &self.timing(), device_addr.into(), data)
   }
 
   /// Write u8 data to a device register
@@ -231,9 +975,14 @@ impl I2cImpl {
   ///     I2C.with_mut(|i2c| i2c.write_register_u8(0x68, 0x20, 12)).unwrap();
   /// # }
   /// ```
-  pub fn write_register_u8(&self, device_addr: u8, reg: u8, data: u8) -> I2cResult<()> {
+  pub fn write_register_u8(
+    &self,
+    device_addr: impl Into<Address>,
+    reg: u8,
+    data: u8,
+  ) -> I2cResult<()> {
     self.is_initializied()?;
-    interface::write_reg_u8(device_addr, reg, data)
+    interface::write_reg_u8(&self.backend, &self.timing(), device_addr.into(), reg, data)
   }
 
   /// Write u16 data to a device register.
@@ -248,14 +997,21 @@ impl I2cImpl {
   ///     I2C.with_mut(|i2c| i2c.write_register_u16(0x68, 0x20, 0x12ab)).unwrap();
   /// # }
   /// ```
-  pub fn write_register_u16(&self, device_addr: u8, reg: u8, data: u16) -> I2cResult<()> {
+  pub fn write_register_u16(
+    &self,
+    device_addr: impl Into<Address>,
+    reg: u8,
+    data: u16,
+  ) -> I2cResult<()> {
     self.is_initializied()?;
-    interface::write_reg_u16(device_addr, reg, data)
+    interface::write_reg_u16(&self.backend, &self.timing(), device_addr.into(), reg, data)
   }
 
   /// Write a u8 array to a device register.
   /// As usually all I²C register are 8 Bit wide this will only properly write the value
   /// if the device supports auto-increment of the actual register while reading
+  /// The data is not limited by the FIFO size of the controller. Data exceeding 65535 bytes is transferred in
+  /// several transfers, each finished with a STOP condition.
   /// # Example
   /// ```no_run
   /// # use ruspiro_i2c::*;
@@ -264,9 +1020,14 @@ impl I2cImpl {
   ///     I2C.with_mut(|i2c| i2c.write_register_buff(0x68, 0x20, &data)).unwrap();
   /// # }
   /// ```
-  pub fn write_register_buff(&self, device_addr: u8, reg: u8, data: &[u8]) -> I2cResult<()> {
+  pub fn write_register_buff(
+    &self,
+    device_addr: impl Into<Address>,
+    reg: u8,
+    data: &[u8],
+  ) -> I2cResult<()> {
     self.is_initializied()?;
-    interface::write_reg_data(device_addr, reg, data)
+    interface::write_reg_data(&self.backend, &self.timing(), device_addr.into(), reg, data)
   }
 
   /// Write a specific register field to a 8 Bit device register.
@@ -284,22 +1045,266 @@ impl I2cImpl {
   /// ```
   pub fn write_register_field(
     &self,
-    device_addr: u8,
+    device_addr: impl Into<Address>,
     reg: u8,
     value: RegisterFieldValue<u8>,
   ) -> I2cResult<()> {
     self.is_initializied()?;
+    let device_addr = device_addr.into();
     let old_value = self.read_register_u8(device_addr, reg)?;
     let new_value = (old_value & !value.mask()) | value.raw_value();
-    interface::write_reg_u8(device_addr, reg, new_value)
+    interface::write_reg_u8(&self.backend, &self.timing(), device_addr, reg, new_value)
+  }
+
+  /// Start writing the data to a device in interrupt driven mode. The function returns once the transfer has been
+  /// started. The transfer is processed by [I2cImpl::handle_interrupt] and its result is available with
+  /// [I2cImpl::transfer_result] once finished. Another transfer could not be started before this result has been
+  /// taken and fails with [I2cError::Busy].
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # fn doc() {
+  ///     I2C.with_mut(|i2c| i2c.start_write(0x68, &[0x20, 12])).unwrap();
+  /// # }
+  /// ```
+  pub fn start_write(&mut self, device_addr: impl Into<Address>, data: &[u8]) -> I2cResult<()> {
+    self.start_write_read(device_addr, data, 0)
+  }
+
+  /// Start reading ``len`` bytes from a device in interrupt driven mode. The data read is returned by
+  /// [I2cImpl::transfer_result] once the transfer has been finished.
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # fn doc() {
+  ///     I2C.with_mut(|i2c| i2c.start_read(0x68, 4)).unwrap();
+  /// # }
+  /// ```
+  pub fn start_read(&mut self, device_addr: impl Into<Address>, len: usize) -> I2cResult<()> {
+    self.start_write_read(device_addr, &[], len)
+  }
+
+  /// Start writing data to a device followed by reading ``len`` bytes from it with a repeated START condition in
+  /// interrupt driven mode. Each phase of the transfer is limited to 65535 bytes. The transfer fails with
+  /// [I2cError::Busy] while the previous one is in progress or its result has not been taken with
+  /// [I2cImpl::transfer_result] yet.
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # fn doc() {
+  ///     // start reading the registers 0x20 to 0x23 and do other work until the transfer has been finished
+  ///     I2C.with_mut(|i2c| i2c.start_write_read(0x68, &[0x20], 4)).unwrap();
+  ///     let data = loop {
+  ///         if let Some(result) = I2C.with_mut(|i2c| i2c.transfer_result()) {
+  ///             break result.unwrap();
+  ///         }
+  ///     };
+  /// # }
+  /// ```
+  ///
+  /// ```
+  /// # use ruspiro_i2c::*;
+  /// # use ruspiro_i2c::backend::sim::*;
+  /// let device = SimRegisterDevice::new(256);
+  /// device.set_register(0x20, 0xAB);
+  /// let bsc = SimulatedBsc::new();
+  /// bsc.attach(0x68, device.clone());
+  /// let mut i2c = I2cImpl::with_backend(bsc);
+  /// i2c.initialize(250_000_000, true).unwrap();
+  ///
+  /// i2c.start_write_read(0x68, &[0x20], 1).unwrap();
+  /// // the few steps of this transfer are done well before the bus has progressed by 100 steps
+  /// for _ in 0..100 {
+  ///     if i2c.backend().interrupt_pending() {
+  ///         i2c.handle_interrupt();
+  ///     }
+  /// }
+  /// // the finished transfer keeps its result until it has been taken
+  /// assert_eq!(i2c.start_write(0x68, &[0x21, 0x01]), Err(I2cError::Busy));
+  /// assert_eq!(i2c.transfer_result(), Some(Ok(vec![0xAB])));
+  /// i2c.start_write(0x68, &[0x21, 0x01]).unwrap();
+  /// ```
+  pub fn start_write_read(
+    &mut self,
+    device_addr: impl Into<Address>,
+    wbuf: &[u8],
+    len: usize,
+  ) -> I2cResult<()> {
+    self.is_initializied()?;
+    let phases = vec![irq::Phase::write(wbuf.to_vec()), irq::Phase::read(len)];
+    let device_addr = device_addr.into();
+    // an interrupt raised while the transfer is started is serviced once the slot is released
+    self
+      .irq_slot()
+      .with(&self.backend, |transfer| {
+        // the result of the previous transfer would be lost
+        if transfer.is_some() {
+          return Err(I2cError::Busy);
+        }
+        *transfer = Some(irq::IrqTransfer::start(
+          &self.backend,
+          device_addr,
+          phases,
+          true,
+        )?);
+        Ok(())
+      })
+      .unwrap_or(Err(I2cError::Busy))
+  }
+
+  /// Service the controller for the interrupt driven transfer in progress. This is the handler to be called for
+  /// the interrupt raised by the BSC controller. See [handle_i2c_interrupt] for the handler serving the memory
+  /// mapped controllers, which does not need access to the I²C bus.
+  pub fn handle_interrupt(&self) {
+    self.irq_slot().service(&self.backend);
+  }
+
+  /// Take the result of the interrupt driven transfer. Returns ``None`` while the transfer is still in progress
+  /// or if there was no transfer started. Once finished the data read from the device is returned.
+  pub fn transfer_result(&mut self) -> Option<I2cResult<Vec<u8>>> {
+    self
+      .irq_slot()
+      .with(&self.backend, |transfer| {
+        let result = transfer.as_mut()?.take_result()?;
+        *transfer = None;
+        Some(result)
+      })
+      .flatten()
+  }
+
+  /// The slot of the interrupt driven transfer shared with the interrupt handler
+  fn irq_slot(&self) -> &irq::IrqSlot {
+    match self.backend.controller() {
+      Some(bsc) => irq::controller_slot(bsc),
+      None => &self.irq,
+    }
+  }
+
+  /// Read a u8 array from a device register asynchronously. The future completes once the controller has finished
+  /// the transfer, so the task does not block while waiting for the device. The buffer is limited to 65535 bytes.
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # async fn doc() {
+  ///     let mut i2c = I2cImpl::new(Bsc::Bsc1);
+  ///     i2c.initialize(250_000_000, true).unwrap();
+  ///     let mut buffer: [u8; 4] = [0; 4];
+  ///     i2c.read_register_buff_async(0x68, 0x20, &mut buffer).await.unwrap();
+  /// # }
+  /// ```
+  pub async fn read_register_buff_async(
+    &mut self,
+    device_addr: impl Into<Address>,
+    reg: u8,
+    buffer: &mut [u8],
+  ) -> I2cResult<usize> {
+    self.is_initializied()?;
+    let phases = vec![irq::Phase::write(vec![reg]), irq::Phase::read(buffer.len())];
+    let data = asynch::transfer(
+      &self.backend,
+      &self.timing(),
+      self.irq_wake,
+      device_addr.into(),
+      phases,
+    )
+    .await?;
+    buffer.copy_from_slice(&data);
+    Ok(buffer.len())
+  }
+
+  /// Write a u8 array to a device register asynchronously. The data is limited to 65534 bytes.
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # async fn doc() {
+  ///     let mut i2c = I2cImpl::new(Bsc::Bsc1);
+  ///     i2c.initialize(250_000_000, true).unwrap();
+  ///     i2c.write_register_buff_async(0x68, 0x20, &[0, 1, 2]).await.unwrap();
+  /// # }
+  /// ```
+  pub async fn write_register_buff_async(
+    &mut self,
+    device_addr: impl Into<Address>,
+    reg: u8,
+    data: &[u8],
+  ) -> I2cResult<()> {
+    self.is_initializied()?;
+    let mut bytes = Vec::with_capacity(data.len() + 1);
+    bytes.push(reg);
+    bytes.extend_from_slice(data);
+    let phases = vec![irq::Phase::write(bytes)];
+    asynch::transfer(
+      &self.backend,
+      &self.timing(),
+      self.irq_wake,
+      device_addr.into(),
+      phases,
+    )
+    .await?;
+    Ok(())
+  }
+
+  /// Process the operations of an ``embedded-hal`` transaction asynchronously. Consecutive operations of the same
+  /// kind are merged and a read following a write is joined with a repeated START condition. A transaction with a
+  /// write following a read fails with [I2cError::UnsupportedTransaction], as the BSC controller is not able to
+  /// join those with a repeated START. The merged operations are limited to 65535 bytes each.
+  /// # Example
+  /// ```no_run
+  /// # use ruspiro_i2c::*;
+  /// # use embedded_hal::i2c::Operation;
+  /// # async fn doc() {
+  ///     let mut i2c = I2cImpl::new(Bsc::Bsc1);
+  ///     i2c.initialize(250_000_000, true).unwrap();
+  ///     let mut buffer: [u8; 2] = [0; 2];
+  ///     i2c.transaction_async(0x68, &mut [Operation::Write(&[0x20]), Operation::Read(&mut buffer)])
+  ///         .await
+  ///         .unwrap();
+  /// # }
+  /// ```
+  pub async fn transaction_async(
+    &mut self,
+    device_addr: impl Into<Address>,
+    operations: &mut [Operation<'_>],
+  ) -> I2cResult<()> {
+    self.is_initializied()?;
+    interface::check_transaction(operations)?;
+    if operations.is_empty() {
+      return Ok(());
+    }
+    let phases = irq::phases(operations);
+    let data = asynch::transfer(
+      &self.backend,
+      &self.timing(),
+      self.irq_wake,
+      device_addr.into(),
+      phases,
+    )
+    .await?;
+    // hand the data read to the buffers of the read operations
+    let mut data = data.into_iter();
+    for operation in operations.iter_mut() {
+      if let Operation::Read(buffer) = operation {
+        for slot in buffer.iter_mut() {
+          *slot = data.next().unwrap_or(0);
+        }
+      }
+    }
+    Ok(())
   }
 
   #[inline(always)]
-  fn is_initializied(&self) -> I2cResult<()> {
+  pub(crate) fn is_initializied(&self) -> I2cResult<()> {
     if !self.initialized {
-      Err("I2C Bus not initialized")
-    } else {
-      Ok(())
+      return Err(I2cError::NotInitialized);
+    }
+    // the bus could not be used while an interrupt driven transfer is in progress
+    let idle = self.irq_slot().with(
+      &self.backend,
+      |transfer| !matches!(transfer, Some(transfer) if !transfer.is_done()),
+    );
+    match idle {
+      Some(true) => Ok(()),
+      _ => Err(I2cError::Busy),
     }
   }
 }
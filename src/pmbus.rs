@@ -0,0 +1,522 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # PMBus command layer
+//!
+//! The Power Management Bus (PMBus) defines the commands of power conversion devices like voltage regulators on top
+//! of the SMBus protocols. The measured values are transferred as words in one of the PMBus data formats:
+//!
+//! - LINEAR11 carries a 5 bit two's complement exponent and an 11 bit two's complement mantissa in each word. It is
+//!   used by most of the measurements like input voltage, currents and temperatures.
+//! - LINEAR16 carries an unsigned 16 bit mantissa, the exponent is given by the ``VOUT_MODE`` of the device. It is
+//!   used for the output voltage related commands.
+//! - DIRECT carries a two's complement value that is converted with the coefficients ``m``, ``b`` and ``R`` given by
+//!   the datasheet of the device.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_i2c::*;
+//! # use ruspiro_i2c::pmbus::{self, Pmbus, StatusWord};
+//! # fn doc() {
+//!     I2C.with_mut(|i2c| {
+//!         let pmbus = Pmbus::new(i2c.smbus().with_pec(true));
+//!         let vin = pmbus.read_linear11(0x40, pmbus::READ_VIN).unwrap();
+//!         // the exponent of the output voltage is taken from VOUT_MODE
+//!         let vout = pmbus.read_vout(0x40).unwrap();
+//!         let status = pmbus.read_status_word(0x40).unwrap();
+//!         if status.contains(StatusWord::VOUT_OV_FAULT) {
+//!             println!("output over voltage at {} V", vout);
+//!         }
+//!     });
+//! # }
+//! ```
+//!
+
+use core::fmt;
+
+use crate::backend::{BscBackend, MmioBackend};
+use crate::smbus::Smbus;
+use crate::{Address, I2cError, I2cResult};
+
+/// Select the page of a multi-output device the paged commands apply to
+pub const PAGE: u8 = 0x00;
+/// Turn the output on or off
+pub const OPERATION: u8 = 0x01;
+/// Configure the combination of CONTROL pin and OPERATION command turning the output on or off
+pub const ON_OFF_CONFIG: u8 = 0x02;
+/// Clear all fault bits of the status registers
+pub const CLEAR_FAULTS: u8 = 0x03;
+/// Protect the device settings against writes
+pub const WRITE_PROTECT: u8 = 0x10;
+/// Store the operating settings in the non-volatile memory
+pub const STORE_DEFAULT_ALL: u8 = 0x11;
+/// Restore the operating settings from the non-volatile memory
+pub const RESTORE_DEFAULT_ALL: u8 = 0x12;
+/// The PMBus commands and data formats supported by the device
+pub const CAPABILITY: u8 = 0x19;
+/// The data format and exponent of the output voltage related commands
+pub const VOUT_MODE: u8 = 0x20;
+/// The output voltage to regulate to
+pub const VOUT_COMMAND: u8 = 0x21;
+/// The upper limit the output voltage could be set to
+pub const VOUT_MAX: u8 = 0x24;
+/// The output voltage while the output is set to margin high
+pub const VOUT_MARGIN_HIGH: u8 = 0x25;
+/// The output voltage while the output is set to margin low
+pub const VOUT_MARGIN_LOW: u8 = 0x26;
+/// The input voltage the device starts converting at
+pub const VIN_ON: u8 = 0x35;
+/// The input voltage the device stops converting at
+pub const VIN_OFF: u8 = 0x36;
+/// The output over voltage fault limit
+pub const VOUT_OV_FAULT_LIMIT: u8 = 0x40;
+/// The output under voltage fault limit
+pub const VOUT_UV_FAULT_LIMIT: u8 = 0x44;
+/// The output over current fault limit
+pub const IOUT_OC_FAULT_LIMIT: u8 = 0x46;
+/// The over temperature fault limit
+pub const OT_FAULT_LIMIT: u8 = 0x4F;
+/// The over temperature warning limit
+pub const OT_WARN_LIMIT: u8 = 0x51;
+/// The input over voltage fault limit
+pub const VIN_OV_FAULT_LIMIT: u8 = 0x55;
+/// The input under voltage fault limit
+pub const VIN_UV_FAULT_LIMIT: u8 = 0x59;
+/// The summary of the device status as byte
+pub const STATUS_BYTE: u8 = 0x78;
+/// The summary of the device status as word
+pub const STATUS_WORD: u8 = 0x79;
+/// The output voltage status
+pub const STATUS_VOUT: u8 = 0x7A;
+/// The output current status
+pub const STATUS_IOUT: u8 = 0x7B;
+/// The input status
+pub const STATUS_INPUT: u8 = 0x7C;
+/// The temperature status
+pub const STATUS_TEMPERATURE: u8 = 0x7D;
+/// The communication, memory and logic status
+pub const STATUS_CML: u8 = 0x7E;
+/// The status of further conditions
+pub const STATUS_OTHER: u8 = 0x7F;
+/// The manufacturer specific status
+pub const STATUS_MFR_SPECIFIC: u8 = 0x80;
+/// The status of the fans 1 and 2
+pub const STATUS_FANS_1_2: u8 = 0x81;
+/// The measured input voltage
+pub const READ_VIN: u8 = 0x88;
+/// The measured input current
+pub const READ_IIN: u8 = 0x89;
+/// The measured output voltage
+pub const READ_VOUT: u8 = 0x8B;
+/// The measured output current
+pub const READ_IOUT: u8 = 0x8C;
+/// The temperature measured by the first sensor
+pub const READ_TEMPERATURE_1: u8 = 0x8D;
+/// The temperature measured by the second sensor
+pub const READ_TEMPERATURE_2: u8 = 0x8E;
+/// The temperature measured by the third sensor
+pub const READ_TEMPERATURE_3: u8 = 0x8F;
+/// The speed of the first fan
+pub const READ_FAN_SPEED_1: u8 = 0x90;
+/// The calculated duty cycle
+pub const READ_DUTY_CYCLE: u8 = 0x94;
+/// The measured switching frequency
+pub const READ_FREQUENCY: u8 = 0x95;
+/// The calculated output power
+pub const READ_POUT: u8 = 0x96;
+/// The calculated input power
+pub const READ_PIN: u8 = 0x97;
+/// The PMBus revision the device complies with
+pub const PMBUS_REVISION: u8 = 0x98;
+/// The manufacturer of the device as block
+pub const MFR_ID: u8 = 0x99;
+/// The model of the device as block
+pub const MFR_MODEL: u8 = 0x9A;
+/// The revision of the device as block
+pub const MFR_REVISION: u8 = 0x9B;
+
+/// The data format of the output voltage related commands given by ``VOUT_MODE``
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoutMode {
+  /// LINEAR16 format with the given exponent
+  Linear { exponent: i8 },
+  /// VID format with the given VID code type
+  Vid { code: u8 },
+  /// DIRECT format with the coefficients given by the datasheet
+  Direct,
+  /// IEEE 754 half precision format
+  IeeeHalf,
+}
+
+impl VoutMode {
+  /// Decode the ``VOUT_MODE`` byte
+  pub fn from_byte(mode: u8) -> I2cResult<Self> {
+    match mode >> 5 {
+      0b000 => Ok(VoutMode::Linear {
+        exponent: sign_extend(mode as u16, 5) as i8,
+      }),
+      0b001 => Ok(VoutMode::Vid { code: mode & 0x1F }),
+      0b010 => Ok(VoutMode::Direct),
+      0b011 => Ok(VoutMode::IeeeHalf),
+      _ => Err(I2cError::UnsupportedVoutMode { mode }),
+    }
+  }
+}
+
+/// The coefficients converting a value in DIRECT format: ``X = (Y * 10^-R - b) / m``
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Direct {
+  /// the slope coefficient
+  pub m: i16,
+  /// the offset
+  pub b: i16,
+  /// the exponent
+  pub r: i8,
+}
+
+impl Direct {
+  /// Create the coefficients as given by the datasheet of a device
+  pub const fn new(m: i16, b: i16, r: i8) -> Self {
+    Direct { m, b, r }
+  }
+
+  /// Convert the raw word of a device into the real world value
+  /// # Example
+  /// ```
+  /// # use ruspiro_i2c::pmbus::Direct;
+  /// let coefficients = Direct::new(100, 0, 2);
+  /// assert_eq!(coefficients.decode(1250), 0.125);
+  /// assert_eq!(coefficients.encode(0.125), 1250);
+  /// ```
+  pub fn decode(self, raw: u16) -> f32 {
+    (raw as i16 as f32 * pow10(-(self.r as i32)) - self.b as f32) / self.m as f32
+  }
+
+  /// Convert the real world value into the raw word of a device
+  pub fn encode(self, value: f32) -> u16 {
+    round((self.m as f32 * value + self.b as f32) * pow10(self.r as i32)) as i16 as u16
+  }
+}
+
+/// Convert a word in LINEAR11 format into the real world value
+/// # Example
+/// ```
+/// # use ruspiro_i2c::pmbus::*;
+/// assert_eq!(linear11_to_f32(0xD3E8), 15.625);
+/// assert_eq!(linear11_to_f32(0xEBFF), 127.875);
+/// assert_eq!(f32_to_linear11(15.625), 0xD3E8);
+/// assert_eq!(linear11_to_f32(f32_to_linear11(-2.5)), -2.5);
+/// ```
+pub fn linear11_to_f32(raw: u16) -> f32 {
+  let exponent = sign_extend(raw >> 11, 5);
+  let mantissa = sign_extend(raw & 0x07FF, 11);
+  mantissa as f32 * pow2(exponent)
+}
+
+/// Convert a real world value into a word in LINEAR11 format. The smallest exponent the mantissa fits in is chosen
+/// to keep the most precision. Values out of the LINEAR11 range are saturated.
+pub fn f32_to_linear11(value: f32) -> u16 {
+  let mut exponent = -16;
+  let mut mantissa = round(value * pow2(-exponent));
+  while !(-1024..=1023).contains(&mantissa) && exponent < 15 {
+    exponent += 1;
+    mantissa = round(value * pow2(-exponent));
+  }
+  let mantissa = mantissa.clamp(-1024, 1023);
+  ((exponent as u16 & 0x1F) << 11) | (mantissa as u16 & 0x07FF)
+}
+
+/// Convert a word in LINEAR16 format with the exponent given by ``VOUT_MODE`` into the real world value
+/// # Example
+/// ```
+/// # use ruspiro_i2c::pmbus::*;
+/// assert_eq!(linear16_to_f32(0x1A00, -12), 1.625);
+/// assert_eq!(f32_to_linear16(1.625, -12), 0x1A00);
+/// // exponents beyond the 5 bit VOUT_MODE range are calculated as well
+/// assert_eq!(linear16_to_f32(1, 64), 18_446_744_073_709_551_616.0);
+/// assert!(linear16_to_f32(1, -128) > 0.0);
+/// assert_eq!(f32_to_linear16(1.0, 127), 0);
+/// assert_eq!(f32_to_linear16(1.0, -128), 0xFFFF);
+/// ```
+pub fn linear16_to_f32(raw: u16, exponent: i8) -> f32 {
+  raw as f32 * pow2(exponent as i32)
+}
+
+/// Convert a real world value into a word in LINEAR16 format with the exponent given by ``VOUT_MODE``. Values out
+/// of the LINEAR16 range are saturated.
+pub fn f32_to_linear16(value: f32, exponent: i8) -> u16 {
+  round(value * pow2(-(exponent as i32))).clamp(0, 0xFFFF) as u16
+}
+
+/// The ``STATUS_WORD`` of a device. The lower byte equals the ``STATUS_BYTE``.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusWord(pub u16);
+
+impl StatusWord {
+  /// An output voltage fault or warning occurred, see ``STATUS_VOUT``
+  pub const VOUT: u16 = 1 << 15;
+  /// An output current or power fault or warning occurred, see ``STATUS_IOUT``
+  pub const IOUT_POUT: u16 = 1 << 14;
+  /// An input voltage, current or power fault or warning occurred, see ``STATUS_INPUT``
+  pub const INPUT: u16 = 1 << 13;
+  /// A manufacturer specific fault or warning occurred, see ``STATUS_MFR_SPECIFIC``
+  pub const MFR_SPECIFIC: u16 = 1 << 12;
+  /// The power good signal is negated
+  pub const POWER_GOOD_NEGATED: u16 = 1 << 11;
+  /// A fan fault or warning occurred, see ``STATUS_FANS_1_2``
+  pub const FANS: u16 = 1 << 10;
+  /// Another fault or warning occurred, see ``STATUS_OTHER``
+  pub const OTHER: u16 = 1 << 9;
+  /// A fault type not given in the other bits occurred
+  pub const UNKNOWN: u16 = 1 << 8;
+  /// The device was busy and unable to respond
+  pub const BUSY: u16 = 1 << 7;
+  /// The output is turned off
+  pub const OFF: u16 = 1 << 6;
+  /// An output over voltage fault occurred
+  pub const VOUT_OV_FAULT: u16 = 1 << 5;
+  /// An output over current fault occurred
+  pub const IOUT_OC_FAULT: u16 = 1 << 4;
+  /// An input under voltage fault occurred
+  pub const VIN_UV_FAULT: u16 = 1 << 3;
+  /// A temperature fault or warning occurred, see ``STATUS_TEMPERATURE``
+  pub const TEMPERATURE: u16 = 1 << 2;
+  /// A communication, memory or logic fault occurred, see ``STATUS_CML``
+  pub const CML: u16 = 1 << 1;
+  /// A fault or warning not listed in the lower byte occurred
+  pub const NONE_OF_THE_ABOVE: u16 = 1 << 0;
+
+  const NAMES: [&str; 16] = [
+    "NONE_OF_THE_ABOVE",
+    "CML",
+    "TEMPERATURE",
+    "VIN_UV_FAULT",
+    "IOUT_OC_FAULT",
+    "VOUT_OV_FAULT",
+    "OFF",
+    "BUSY",
+    "UNKNOWN",
+    "OTHER",
+    "FANS",
+    "POWER_GOOD_NEGATED",
+    "MFR_SPECIFIC",
+    "INPUT",
+    "IOUT_POUT",
+    "VOUT",
+  ];
+
+  /// Whether all of the given flags are set
+  /// # Example
+  /// ```
+  /// # use ruspiro_i2c::pmbus::StatusWord;
+  /// let status = StatusWord(0x8020);
+  /// assert!(status.contains(StatusWord::VOUT | StatusWord::VOUT_OV_FAULT));
+  /// assert!(!status.contains(StatusWord::OFF));
+  /// assert_eq!(format!("{}", status), "VOUT_OV_FAULT | VOUT");
+  /// ```
+  pub fn contains(self, flags: u16) -> bool {
+    self.0 & flags == flags
+  }
+
+  /// Whether no fault or warning is flagged
+  pub fn is_ok(self) -> bool {
+    self.0 == 0
+  }
+}
+
+impl fmt::Display for StatusWord {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut flags = (0..16).filter(|bit| self.0 & (1 << bit) != 0);
+    match flags.next() {
+      Some(bit) => write!(f, "{}", Self::NAMES[bit])?,
+      None => return write!(f, "OK"),
+    }
+    for bit in flags {
+      write!(f, " | {}", Self::NAMES[bit])?;
+    }
+    Ok(())
+  }
+}
+
+/// The PMBus commands on top of the SMBus protocols
+pub struct Pmbus<'a, B = MmioBackend> {
+  smbus: Smbus<'a, B>,
+}
+
+impl<'a, B: BscBackend> Pmbus<'a, B> {
+  /// Use the PMBus commands with the given SMBus, which may have Packet Error Checking enabled
+  pub fn new(smbus: Smbus<'a, B>) -> Self {
+    Pmbus { smbus }
+  }
+
+  /// Access the underlying SMBus to send commands not covered here
+  pub fn smbus(&self) -> &Smbus<'a, B> {
+    &self.smbus
+  }
+
+  /// Select the page of a multi-output device the following paged commands apply to
+  pub fn set_page(&self, addr: impl Into<Address>, page: u8) -> I2cResult<()> {
+    self.smbus.write_byte_data(addr, PAGE, page)
+  }
+
+  /// Clear all fault bits of the status registers of the device
+  pub fn clear_faults(&self, addr: impl Into<Address>) -> I2cResult<()> {
+    self.smbus.send_byte(addr, CLEAR_FAULTS)
+  }
+
+  /// Read the data format of the output voltage related commands
+  pub fn read_vout_mode(&self, addr: impl Into<Address>) -> I2cResult<VoutMode> {
+    VoutMode::from_byte(self.smbus.read_byte_data(addr, VOUT_MODE)?)
+  }
+
+  /// Read a value in LINEAR11 format, e.g. [READ_VIN], [READ_IOUT] or [READ_TEMPERATURE_1]
+  pub fn read_linear11(&self, addr: impl Into<Address>, command: u8) -> I2cResult<f32> {
+    Ok(linear11_to_f32(self.smbus.read_word_data(addr, command)?))
+  }
+
+  /// Write a value in LINEAR11 format, e.g. [VIN_ON] or [OT_FAULT_LIMIT]
+  pub fn write_linear11(&self, addr: impl Into<Address>, command: u8, value: f32) -> I2cResult<()> {
+    self
+      .smbus
+      .write_word_data(addr, command, f32_to_linear11(value))
+  }
+
+  /// Read an output voltage related value in LINEAR16 format, e.g. [READ_VOUT] or [VOUT_MAX]. The exponent is
+  /// taken from ``VOUT_MODE``, a device not using the LINEAR16 format fails with [I2cError::UnsupportedVoutMode].
+  pub fn read_linear16(&self, addr: impl Into<Address>, command: u8) -> I2cResult<f32> {
+    let addr = addr.into();
+    let exponent = self.vout_exponent(addr)?;
+    Ok(linear16_to_f32(
+      self.smbus.read_word_data(addr, command)?,
+      exponent,
+    ))
+  }
+
+  /// Write an output voltage related value in LINEAR16 format, e.g. [VOUT_COMMAND], with the exponent taken from
+  /// ``VOUT_MODE``
+  pub fn write_linear16(&self, addr: impl Into<Address>, command: u8, value: f32) -> I2cResult<()> {
+    let addr = addr.into();
+    let exponent = self.vout_exponent(addr)?;
+    self
+      .smbus
+      .write_word_data(addr, command, f32_to_linear16(value, exponent))
+  }
+
+  /// Read a value in DIRECT format with the coefficients given by the datasheet of the device
+  pub fn read_direct(
+    &self,
+    addr: impl Into<Address>,
+    command: u8,
+    coefficients: Direct,
+  ) -> I2cResult<f32> {
+    Ok(coefficients.decode(self.smbus.read_word_data(addr, command)?))
+  }
+
+  /// Write a value in DIRECT format with the coefficients given by the datasheet of the device
+  pub fn write_direct(
+    &self,
+    addr: impl Into<Address>,
+    command: u8,
+    coefficients: Direct,
+    value: f32,
+  ) -> I2cResult<()> {
+    self
+      .smbus
+      .write_word_data(addr, command, coefficients.encode(value))
+  }
+
+  /// Read the measured input voltage in volts
+  pub fn read_vin(&self, addr: impl Into<Address>) -> I2cResult<f32> {
+    self.read_linear11(addr, READ_VIN)
+  }
+
+  /// Read the measured output voltage in volts
+  /// # Example
+  /// ```
+  /// # use ruspiro_i2c::I2cImpl;
+  /// # use ruspiro_i2c::backend::sim::*;
+  /// # use ruspiro_i2c::pmbus::{self, Pmbus};
+  /// let device = SimRegisterDevice::new(256);
+  /// let bsc = SimulatedBsc::new();
+  /// bsc.attach(0x40, device.clone());
+  /// let mut i2c = I2cImpl::with_backend(bsc);
+  /// i2c.initialize(250_000_000, true).unwrap();
+  /// // LINEAR16 with the exponent -12 and the raw output voltage 0x1A00
+  /// device.set_register(pmbus::VOUT_MODE as usize, 0x14);
+  /// device.set_register(pmbus::READ_VOUT as usize, 0x00);
+  /// device.set_register(pmbus::READ_VOUT as usize + 1, 0x1A);
+  /// let pmbus = Pmbus::new(i2c.smbus());
+  /// assert_eq!(pmbus.read_vout(0x40).unwrap(), 1.625);
+  /// ```
+  pub fn read_vout(&self, addr: impl Into<Address>) -> I2cResult<f32> {
+    self.read_linear16(addr, READ_VOUT)
+  }
+
+  /// Read the measured output current in amperes
+  pub fn read_iout(&self, addr: impl Into<Address>) -> I2cResult<f32> {
+    self.read_linear11(addr, READ_IOUT)
+  }
+
+  /// Read the output power in watts
+  pub fn read_pout(&self, addr: impl Into<Address>) -> I2cResult<f32> {
+    self.read_linear11(addr, READ_POUT)
+  }
+
+  /// Read the temperature of the first sensor in degree Celsius
+  pub fn read_temperature_1(&self, addr: impl Into<Address>) -> I2cResult<f32> {
+    self.read_linear11(addr, READ_TEMPERATURE_1)
+  }
+
+  /// Set the output voltage to regulate to in volts
+  pub fn set_vout(&self, addr: impl Into<Address>, volts: f32) -> I2cResult<()> {
+    self.write_linear16(addr, VOUT_COMMAND, volts)
+  }
+
+  /// Read the summary of the device status
+  pub fn read_status_word(&self, addr: impl Into<Address>) -> I2cResult<StatusWord> {
+    Ok(StatusWord(self.smbus.read_word_data(addr, STATUS_WORD)?))
+  }
+
+  fn vout_exponent(&self, addr: Address) -> I2cResult<i8> {
+    let mode = self.smbus.read_byte_data(addr, VOUT_MODE)?;
+    match VoutMode::from_byte(mode)? {
+      VoutMode::Linear { exponent } => Ok(exponent),
+      _ => Err(I2cError::UnsupportedVoutMode { mode }),
+    }
+  }
+}
+
+/// Interpret the lower ``bits`` of the value as two's complement number
+fn sign_extend(value: u16, bits: u32) -> i32 {
+  let shift = 32 - bits;
+  ((value as i32) << shift) >> shift
+}
+
+/// 2 to the power of ``exponent`` as the float functions are not available without the standard library
+fn pow2(exponent: i32) -> f32 {
+  let factor = if exponent >= 0 { 2.0 } else { 0.5 };
+  (0..exponent.abs()).fold(1.0, |value, _| value * factor)
+}
+
+/// 10 to the power of ``exponent``
+fn pow10(exponent: i32) -> f32 {
+  let value = (0..exponent.abs()).fold(1.0, |value, _| value * 10.0);
+  if exponent >= 0 {
+    value
+  } else {
+    1.0 / value
+  }
+}
+
+/// Round to the nearest integer, halfway cases away from zero
+fn round(value: f32) -> i32 {
+  if value < 0.0 {
+    (value - 0.5) as i32
+  } else {
+    (value + 0.5) as i32
+  }
+}
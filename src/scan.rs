@@ -0,0 +1,131 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Bus scan
+//!
+//! The configuration of a scan for devices on the I²C bus. Like ``i2cdetect`` each address is probed either with a
+//! SMBus Quick Write, which addresses the device without transferring any data, or with a read of a single byte.
+//! Some devices are confused by one of these probes: a Quick Write may lock the write protection of an EEPROM, while
+//! a read may corrupt the state of a write only device. The [ScanMode::Auto] mode therefore reads from the EEPROM
+//! address ranges 0x30 to 0x37 and 0x50 to 0x5F and uses a Quick Write for all other addresses.
+//!
+//! # Example
+//! ```
+//! # use ruspiro_i2c::*;
+//! # use ruspiro_i2c::backend::sim::*;
+//! let bsc = SimulatedBsc::new();
+//! bsc.attach(0x50, SimRegisterDevice::new(256));
+//! let mut i2c = I2cImpl::with_backend(bsc);
+//! i2c.initialize(250_000_000, true).unwrap();
+//!
+//! let config = ScanConfig::new().with_range(0x48, 0x57);
+//! let result = i2c.scan_with(&config).unwrap();
+//! assert_eq!(result.len(), 16);
+//! assert_eq!(result[8], (SevenBitAddress::new(0x50).unwrap(), Probe::Acknowledged));
+//! assert_eq!(result[0].1, Probe::Absent);
+//! ```
+//!
+
+use core::time::Duration;
+
+/// The way each address is probed during a scan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanMode {
+  /// Probe with a SMBus Quick Write addressing the device without transferring any data
+  QuickWrite,
+  /// Probe with a read of a single byte
+  Read,
+  /// Probe the EEPROM address ranges with a read and all other addresses with a Quick Write
+  Auto,
+}
+
+impl ScanMode {
+  /// Whether the given address is probed with a read in this mode
+  pub(crate) fn reads(self, addr: u8) -> bool {
+    match self {
+      ScanMode::QuickWrite => false,
+      ScanMode::Read => true,
+      ScanMode::Auto => matches!(addr, 0x30..=0x37 | 0x50..=0x5F),
+    }
+  }
+}
+
+/// The outcome of probing a single address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Probe {
+  /// A device acknowledged its address
+  Acknowledged,
+  /// The probe did not finish in time, e.g. because a device kept stretching the clock or holds the bus
+  Busy,
+  /// No device acknowledged the address
+  Absent,
+}
+
+/// The configuration of a scan for devices with [I2cImpl::scan_with](crate::I2cImpl::scan_with)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanConfig {
+  /// The way each address is probed
+  pub mode: ScanMode,
+  /// The first address probed
+  pub first: u8,
+  /// The last address probed
+  pub last: u8,
+  /// Probe the reserved addresses within the range as well
+  pub include_reserved: bool,
+  /// The timeout of each probe, if not given the timeout of the bus applies
+  pub timeout: Option<Duration>,
+}
+
+impl ScanConfig {
+  /// Create the configuration probing all regular 7 bit addresses in [ScanMode::Auto] mode
+  pub const fn new() -> Self {
+    ScanConfig {
+      mode: ScanMode::Auto,
+      first: 0x00,
+      last: 0x7F,
+      include_reserved: false,
+      timeout: None,
+    }
+  }
+
+  /// Probe each address in the given way
+  pub const fn with_mode(self, mode: ScanMode) -> Self {
+    ScanConfig { mode, ..self }
+  }
+
+  /// Probe the addresses from ``first`` to ``last`` including both
+  pub const fn with_range(self, first: u8, last: u8) -> Self {
+    ScanConfig {
+      first,
+      last,
+      ..self
+    }
+  }
+
+  /// Probe the reserved addresses within the range as well, see
+  /// [SevenBitAddress::is_reserved](crate::SevenBitAddress::is_reserved)
+  pub const fn with_reserved(self, include_reserved: bool) -> Self {
+    ScanConfig {
+      include_reserved,
+      ..self
+    }
+  }
+
+  /// Give up each probe after the given time and report the address as [Probe::Busy]
+  pub const fn with_timeout(self, timeout: Duration) -> Self {
+    ScanConfig {
+      timeout: Some(timeout),
+      ..self
+    }
+  }
+}
+
+impl Default for ScanConfig {
+  fn default() -> Self {
+    ScanConfig::new()
+  }
+}
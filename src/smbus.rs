@@ -0,0 +1,618 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # SMBus protocol
+//!
+//! The System Management Bus (SMBus 2.0) is based on the I²C bus and defines the framing of the transfers to a
+//! device. Each protocol is mapped to the corresponding sequence of START and repeated START conditions on the I²C
+//! bus. Words are transferred in little endian byte order, other than [I2cImpl::read_register_u16] and
+//! [I2cImpl::write_register_u16], which use big endian byte order.
+//!
+//! The BSC controller needs to know the number of bytes to read before the read is started and is not able to adapt
+//! it within a transfer. As the byte count of a block is sent by the device within the same transfer, a Block Read
+//! is not processed as the single transfer the SMBus defines. The byte count is read on its own first, finished with
+//! a STOP condition, and the block is read with exactly the byte count announced afterwards, so the device need to
+//! provide the same block on both reads. The Block Process Call can not repeat the block written without the device
+//! processing it twice, so it reads and acknowledges the maximum block size of [BLOCK_MAX] bytes and the PEC
+//! following the byte count and drops the surplus bytes.
+//!
+//! With Packet Error Checking enabled by [Smbus::with_pec] each write is followed by the Packet Error Code (PEC) and
+//! the PEC sent by the device after the data read is verified. The PEC is the CRC-8 calculated with [pec] over all
+//! bytes of the transfer including the address bytes. A mismatch fails with [I2cError::PecMismatch].
+//!
+//! A device signals an alert by pulling the shared SMBALERT# line low. The host then reads a byte from the Alert
+//! Response Address [ALERT_RESPONSE_ADDRESS] and the alerting device responds with its own address. If several
+//! devices signal an alert at the same time the device with the lowest address wins the arbitration, releases the
+//! line and stops responding, so [Smbus::alert_responses] reads the Alert Response Address until no device responds
+//! anymore. [SmbusAlert] binds the SMBALERT# line to a GPIO pin and dispatches the alerts to per device handlers.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_i2c::*;
+//! # fn doc() {
+//!     I2C.with_mut(|i2c| {
+//!         let smbus = i2c.smbus();
+//!         // read the voltage of a smart battery
+//!         let voltage = smbus.read_word_data(0x0B, 0x09).unwrap();
+//!         let name = smbus.block_read(0x0B, 0x21).unwrap();
+//!     });
+//! # }
+//! ```
+//!
+//! Packet Error Checking with a simulated device providing the PEC of the read in the register following the data:
+//! ```
+//! # use ruspiro_i2c::I2cImpl;
+//! # use ruspiro_i2c::backend::sim::*;
+//! let device = SimRegisterDevice::new(256);
+//! let bsc = SimulatedBsc::new();
+//! bsc.attach(0x0B, device.clone());
+//! let mut i2c = I2cImpl::with_backend(bsc);
+//! i2c.initialize(250_000_000, true).unwrap();
+//! let smbus = i2c.smbus().with_pec(true);
+//!
+//! smbus.write_byte_data(0x0B, 0x10, 0x42).unwrap();
+//! assert_eq!(device.register(0x11), 0x41);
+//!
+//! device.set_register(0x11, 0x44);
+//! assert_eq!(smbus.read_byte_data(0x0B, 0x10).unwrap(), 0x42);
+//! device.set_register(0x11, 0x00);
+//! assert!(smbus.read_byte_data(0x0B, 0x10).is_err());
+//! ```
+//!
+//! The START and STOP conditions and the bytes of each protocol as seen by a simulated device:
+//! ```
+//! # use ruspiro_i2c::*;
+//! # use ruspiro_i2c::backend::sim::*;
+//! # use ruspiro_i2c::smbus::{pec, BLOCK_MAX};
+//! use SimEvent::{Read, Start, Stop, Write};
+//!
+//! let device = SimRegisterDevice::new(256);
+//! let recorder = SimRecorder::new(device.clone());
+//! let bsc = SimulatedBsc::new();
+//! bsc.attach(0x0B, recorder.clone());
+//! let mut i2c = I2cImpl::with_backend(bsc);
+//! i2c.initialize(250_000_000, true).unwrap();
+//! let smbus = i2c.smbus();
+//! let seen = |expected: &[SimEvent]| {
+//!     assert_eq!(recorder.events(), expected);
+//!     recorder.clear();
+//! };
+//!
+//! smbus.quick_command(0x0B, false).unwrap();
+//! seen(&[Start { read: false }, Stop]);
+//! smbus.quick_command(0x0B, true).unwrap();
+//! seen(&[Start { read: true }, Stop]);
+//! smbus.send_byte(0x0B, 0x10).unwrap();
+//! seen(&[Start { read: false }, Write(0x10), Stop]);
+//! device.set_register(0x10, 0x42);
+//! assert_eq!(smbus.receive_byte(0x0B).unwrap(), 0x42);
+//! seen(&[Start { read: true }, Read(0x42), Stop]);
+//!
+//! smbus.write_byte_data(0x0B, 0x20, 0x55).unwrap();
+//! seen(&[Start { read: false }, Write(0x20), Write(0x55), Stop]);
+//! assert_eq!(smbus.read_byte_data(0x0B, 0x20).unwrap(), 0x55);
+//! seen(&[Start { read: false }, Write(0x20), Start { read: true }, Read(0x55), Stop]);
+//!
+//! // words are transferred with the low byte first
+//! smbus.write_word_data(0x0B, 0x30, 0x1234).unwrap();
+//! seen(&[Start { read: false }, Write(0x30), Write(0x34), Write(0x12), Stop]);
+//! assert_eq!(smbus.read_word_data(0x0B, 0x30).unwrap(), 0x1234);
+//! seen(&[Start { read: false }, Write(0x30), Start { read: true }, Read(0x34), Read(0x12), Stop]);
+//! device.set_register(0x42, 0xCD);
+//! device.set_register(0x43, 0xAB);
+//! assert_eq!(smbus.process_call(0x0B, 0x40, 0xBEEF).unwrap(), 0xABCD);
+//! seen(&[
+//!     Start { read: false },
+//!     Write(0x40),
+//!     Write(0xEF),
+//!     Write(0xBE),
+//!     Start { read: true },
+//!     Read(0xCD),
+//!     Read(0xAB),
+//!     Stop,
+//! ]);
+//!
+//! // a block is preceded by its byte count, which a block read reads on its own first
+//! smbus.block_write(0x0B, 0x50, &[1, 2, 3]).unwrap();
+//! seen(&[Start { read: false }, Write(0x50), Write(3), Write(1), Write(2), Write(3), Stop]);
+//! assert_eq!(smbus.block_read(0x0B, 0x50).unwrap(), [1, 2, 3]);
+//! seen(&[
+//!     Start { read: false },
+//!     Write(0x50),
+//!     Start { read: true },
+//!     Read(3),
+//!     Stop,
+//!     Start { read: false },
+//!     Write(0x50),
+//!     Start { read: true },
+//!     Read(3),
+//!     Read(1),
+//!     Read(2),
+//!     Read(3),
+//!     Stop,
+//! ]);
+//! // with Packet Error Checking the PEC following the block is read as well
+//! let code = pec(&[0x0B << 1, 0x50, 0x0B << 1 | 1, 3, 1, 2, 3]);
+//! device.set_register(0x54, code);
+//! assert_eq!(i2c.smbus().with_pec(true).block_read(0x0B, 0x50).unwrap(), [1, 2, 3]);
+//! assert_eq!(
+//!     recorder.events()[5..],
+//!     [
+//!         Start { read: false },
+//!         Write(0x50),
+//!         Start { read: true },
+//!         Read(3),
+//!         Read(1),
+//!         Read(2),
+//!         Read(3),
+//!         Read(code),
+//!         Stop,
+//!     ]
+//! );
+//! recorder.clear();
+//!
+//! // a block process call reads the maximum block size and the PEC
+//! device.set_register(0x62, 1);
+//! device.set_register(0x63, 0x77);
+//! assert_eq!(smbus.block_process_call(0x0B, 0x60, &[9]).unwrap(), [0x77]);
+//! let events = recorder.events();
+//! assert_eq!(
+//!     events[..7],
+//!     [Start { read: false }, Write(0x60), Write(1), Write(9), Start { read: true }, Read(1), Read(0x77)]
+//! );
+//! assert_eq!(events.len(), 6 + BLOCK_MAX + 2);
+//! ```
+//!
+
+extern crate alloc;
+use alloc::{boxed::Box, sync::Arc, vec, vec::Vec};
+use core::sync::atomic::{AtomicBool, Ordering};
+use ruspiro_gpio::{function::Input, pud::PullUp, GpioEvent, Pin, GPIO};
+
+use crate::backend::{BscBackend, MmioBackend};
+use crate::{interface, Address, I2cError, I2cImpl, I2cResult, SevenBitAddress};
+
+/// The maximum number of bytes transferred by a block write or read
+pub const BLOCK_MAX: usize = 32;
+
+/// The address a device signalling an alert on the SMBALERT# line responds to with its own address
+pub const ALERT_RESPONSE_ADDRESS: u8 = 0x0C;
+
+const CRC8_POLYNOMIAL: u8 = 0x07; // x⁸ + x² + x + 1
+
+/// Calculate the SMBus Packet Error Code, which is the CRC-8 with the polynomial x⁸ + x² + x + 1 and the initial
+/// value 0 over the given bytes.
+/// # Example
+/// ```
+/// # use ruspiro_i2c::smbus::pec;
+/// assert_eq!(pec(b"123456789"), 0xF4);
+/// // write byte 0x42 to command 0x10 of the device at 0x0B
+/// assert_eq!(pec(&[0x0B << 1, 0x10, 0x42]), 0x41);
+/// ```
+pub fn pec(data: &[u8]) -> u8 {
+  crc8(0, data)
+}
+
+fn crc8(crc: u8, data: &[u8]) -> u8 {
+  data.iter().fold(crc, |crc, &byte| {
+    (0..8).fold(crc ^ byte, |crc, _| {
+      if crc & 0x80 != 0 {
+        (crc << 1) ^ CRC8_POLYNOMIAL
+      } else {
+        crc << 1
+      }
+    })
+  })
+}
+
+/// The SMBus protocols on top of an I²C bus
+pub struct Smbus<'a, B = MmioBackend> {
+  i2c: &'a I2cImpl<B>,
+  pec: bool,
+}
+
+impl<B: BscBackend> I2cImpl<B> {
+  /// Access the devices on this bus with the SMBus protocols
+  pub fn smbus(&self) -> Smbus<'_, B> {
+    Smbus {
+      i2c: self,
+      pec: false,
+    }
+  }
+}
+
+impl<B: BscBackend> Smbus<'_, B> {
+  /// Enable or disable the Packet Error Checking of all transfers except the Quick Command
+  pub fn with_pec(self, enabled: bool) -> Self {
+    Smbus {
+      pec: enabled,
+      ..self
+    }
+  }
+
+  /// Quick Command: address the device without any data, the R/W bit carries the single bit of information
+  pub fn quick_command(&self, addr: impl Into<Address>, read: bool) -> I2cResult<()> {
+    self.i2c.is_initializied()?;
+    let timing = self.i2c.timing();
+    if read {
+      interface::read_data(&self.i2c.backend, &timing, addr.into(), &mut []).map(|_| ())
+    } else {
+      interface::write_data(&self.i2c.backend, &timing, addr.into(), &[])
+    }
+  }
+
+  /// Send Byte: write a single byte to the device
+  pub fn send_byte(&self, addr: impl Into<Address>, data: u8) -> I2cResult<()> {
+    self.write(addr.into(), &[data])
+  }
+
+  /// Receive Byte: read a single byte from the device
+  pub fn receive_byte(&self, addr: impl Into<Address>) -> I2cResult<u8> {
+    let mut buffer = [0; 1];
+    self.read(addr.into(), &mut buffer)?;
+    Ok(buffer[0])
+  }
+
+  /// Write Byte: write a byte to the device following the command code
+  pub fn write_byte_data(&self, addr: impl Into<Address>, command: u8, data: u8) -> I2cResult<()> {
+    self.write(addr.into(), &[command, data])
+  }
+
+  /// Read Byte: read a byte from the device after writing the command code
+  pub fn read_byte_data(&self, addr: impl Into<Address>, command: u8) -> I2cResult<u8> {
+    let mut buffer = [0; 1];
+    self.write_read(addr.into(), &[command], &mut buffer)?;
+    Ok(buffer[0])
+  }
+
+  /// Write Word: write a little endian word to the device following the command code
+  pub fn write_word_data(&self, addr: impl Into<Address>, command: u8, data: u16) -> I2cResult<()> {
+    let [low, high] = data.to_le_bytes();
+    self.write(addr.into(), &[command, low, high])
+  }
+
+  /// Read Word: read a little endian word from the device after writing the command code
+  pub fn read_word_data(&self, addr: impl Into<Address>, command: u8) -> I2cResult<u16> {
+    let mut buffer = [0; 2];
+    self.write_read(addr.into(), &[command], &mut buffer)?;
+    Ok(u16::from_le_bytes(buffer))
+  }
+
+  /// Process Call: write a word to the device following the command code and read the word the device responds
+  /// with after a repeated START condition
+  pub fn process_call(&self, addr: impl Into<Address>, command: u8, data: u16) -> I2cResult<u16> {
+    let [low, high] = data.to_le_bytes();
+    let mut buffer = [0; 2];
+    self.write_read(addr.into(), &[command, low, high], &mut buffer)?;
+    Ok(u16::from_le_bytes(buffer))
+  }
+
+  /// Block Write: write the command code, the byte count and up to [BLOCK_MAX] bytes of data to the device
+  pub fn block_write(&self, addr: impl Into<Address>, command: u8, data: &[u8]) -> I2cResult<()> {
+    let request = block_request(command, data)?;
+    self.write(addr.into(), &request)
+  }
+
+  /// Block Read: read the byte count and the data of a block from the device after writing the command code. The
+  /// byte count is read in a transfer of its own first, see the [module documentation](self).
+  pub fn block_read(&self, addr: impl Into<Address>, command: u8) -> I2cResult<Vec<u8>> {
+    let addr = addr.into();
+    let mut count = [0; 1];
+    self.transfer(addr, &[command], &mut count)?;
+    let len = count[0] as usize;
+    if len > BLOCK_MAX {
+      return Err(I2cError::InvalidLength { len });
+    }
+    let mut buffer = vec![0; len + 1 + self.pec as usize];
+    self.transfer(addr, &[command], &mut buffer)?;
+    // the length of the read no longer matches the block once the device changes it in between
+    if buffer[0] != count[0] {
+      return Err(I2cError::InvalidLength {
+        len: buffer[0] as usize,
+      });
+    }
+    self.check_pec(addr, &[command], &buffer[..=len], buffer.get(len + 1))?;
+    Ok(buffer[1..=len].to_vec())
+  }
+
+  /// Block Write-Block Read Process Call: write a block of data to the device following the command code and read
+  /// the block the device responds with after a repeated START condition. As the byte count of the response is not
+  /// known before the read is started, [BLOCK_MAX] bytes and the PEC are read and acknowledged following the byte
+  /// count, see the [module documentation](self).
+  pub fn block_process_call(
+    &self,
+    addr: impl Into<Address>,
+    command: u8,
+    data: &[u8],
+  ) -> I2cResult<Vec<u8>> {
+    let request = block_request(command, data)?;
+    self.block_read_from(addr.into(), &request)
+  }
+
+  /// Read the Alert Response Address to find the device that signalled an alert. Returns `None` if no device
+  /// responds, i.e. no alert is pending.
+  pub fn alert_response(&self) -> I2cResult<Option<SevenBitAddress>> {
+    let mut buffer = [0; 1];
+    match self.read(Address::SevenBit(ALERT_RESPONSE_ADDRESS), &mut buffer) {
+      // the device responds with its address in the upper 7 bits
+      Ok(()) => Ok(Some(SevenBitAddress::from_shifted(buffer[0]))),
+      Err(I2cError::AddressNack { .. }) => Ok(None),
+      Err(error) => Err(error),
+    }
+  }
+
+  /// Read the Alert Response Address until no device responds anymore and return the addresses of all devices that
+  /// signalled an alert in the order they won the arbitration. A device responding a second time does not release
+  /// its alert and ends the loop, as it would respond again to each further read.
+  pub fn alert_responses(&self) -> I2cResult<Vec<SevenBitAddress>> {
+    let mut alerts = Vec::new();
+    while let Some(addr) = self.alert_response()? {
+      if alerts.contains(&addr) {
+        break;
+      }
+      alerts.push(addr);
+    }
+    Ok(alerts)
+  }
+
+  fn write(&self, addr: Address, data: &[u8]) -> I2cResult<()> {
+    self.i2c.is_initializied()?;
+    let timing = self.i2c.timing();
+    if !self.pec {
+      return interface::write_data(&self.i2c.backend, &timing, addr, data);
+    }
+    let mut message = data.to_vec();
+    message.push(message_pec(addr, data, None));
+    interface::write_data(&self.i2c.backend, &timing, addr, &message)
+  }
+
+  fn read(&self, addr: Address, buffer: &mut [u8]) -> I2cResult<()> {
+    self.write_read(addr, &[], buffer)
+  }
+
+  /// Write the data and read a fixed number of bytes from the device. Without any data to write this is a read.
+  fn write_read(&self, addr: Address, wbuf: &[u8], rbuf: &mut [u8]) -> I2cResult<()> {
+    let len = rbuf.len() + self.pec as usize;
+    let mut buffer = vec![0; len];
+    self.transfer(addr, wbuf, &mut buffer)?;
+    self.check_pec(addr, wbuf, &buffer[..rbuf.len()], buffer.get(rbuf.len()))?;
+    rbuf.copy_from_slice(&buffer[..rbuf.len()]);
+    Ok(())
+  }
+
+  /// Write the data and read a block starting with its byte count from the device in a single transfer reading the
+  /// maximum block size
+  fn block_read_from(&self, addr: Address, wbuf: &[u8]) -> I2cResult<Vec<u8>> {
+    let mut buffer = [0; BLOCK_MAX + 2];
+    self.transfer(addr, wbuf, &mut buffer)?;
+    let len = buffer[0] as usize;
+    if len > BLOCK_MAX {
+      return Err(I2cError::InvalidLength { len });
+    }
+    self.check_pec(addr, wbuf, &buffer[..=len], buffer.get(len + 1))?;
+    Ok(buffer[1..=len].to_vec())
+  }
+
+  fn transfer(&self, addr: Address, wbuf: &[u8], rbuf: &mut [u8]) -> I2cResult<()> {
+    self.i2c.is_initializied()?;
+    let timing = self.i2c.timing();
+    if wbuf.is_empty() {
+      interface::read_data(&self.i2c.backend, &timing, addr, rbuf).map(|_| ())
+    } else {
+      interface::write_read(&self.i2c.backend, &timing, addr, wbuf, rbuf).map(|_| ())
+    }
+  }
+
+  /// Verify the PEC received after the data read if Packet Error Checking is enabled
+  fn check_pec(
+    &self,
+    addr: Address,
+    wbuf: &[u8],
+    data: &[u8],
+    received: Option<&u8>,
+  ) -> I2cResult<()> {
+    match received {
+      Some(&received) if self.pec && received != message_pec(addr, wbuf, Some(data)) => {
+        Err(I2cError::PecMismatch { addr })
+      }
+      _ => Ok(()),
+    }
+  }
+}
+
+/// Calculate the PEC over the address and data bytes of a transfer writing ``wbuf`` and optionally reading ``rbuf``
+/// after a repeated START condition
+fn message_pec(addr: Address, wbuf: &[u8], rbuf: Option<&[u8]>) -> u8 {
+  let header = addr.header() << 1;
+  let mut crc = 0;
+  if !wbuf.is_empty() || rbuf.is_none() {
+    crc = crc8(crc, &[header]);
+    if let Some(low) = addr.low_byte() {
+      crc = crc8(crc, &[low]);
+    }
+    crc = crc8(crc, wbuf);
+  }
+  if let Some(rbuf) = rbuf {
+    crc = crc8(crc, &[header | 1]);
+    crc = crc8(crc, rbuf);
+  }
+  crc
+}
+
+/// The bytes written for a block: the command code, the byte count and the data
+fn block_request(command: u8, data: &[u8]) -> I2cResult<Vec<u8>> {
+  if data.len() > BLOCK_MAX {
+    return Err(I2cError::InvalidLength { len: data.len() });
+  }
+  let mut request = vec![command, data.len() as u8];
+  request.extend_from_slice(data);
+  Ok(request)
+}
+
+/// The handler of an alert signalled by a device. It gets the SMBus passed to clear the cause of the alert.
+type AlertHandler<B> = Box<dyn FnMut(&Smbus<'_, B>, SevenBitAddress)>;
+
+/// The SMBALERT# line with the handlers of the alerts signalled by the devices on the bus. The line is either bound
+/// to a GPIO pin, where the falling edge marks an alert as pending, or the alert is reported with [SmbusAlert::signal]
+/// by other means. Pending alerts are handled with [SmbusAlert::handle] outside of the interrupt context as this
+/// requires the I²C bus.
+/// # Example
+/// ```no_run
+/// # use ruspiro_i2c::*;
+/// # use ruspiro_i2c::smbus::SmbusAlert;
+/// # fn doc() {
+///     let mut alert = SmbusAlert::with_pin(4).unwrap();
+///     alert.on(0x4C, |smbus, addr| {
+///         // reading the status of the temperature monitor clears its alert
+///         let status = smbus.read_byte_data(addr, 0x02).unwrap();
+///     })
+///     .unwrap();
+///     loop {
+///         I2C.with_mut(|i2c| alert.handle(&i2c.smbus()).unwrap());
+///     }
+/// # }
+/// ```
+pub struct SmbusAlert<B = MmioBackend> {
+  pin: Option<Pin<Input, PullUp>>,
+  pin_num: u32,
+  pending: Arc<AtomicBool>,
+  handlers: Vec<(SevenBitAddress, AlertHandler<B>)>,
+  unhandled: Option<AlertHandler<B>>,
+}
+
+impl<B: BscBackend> SmbusAlert<B> {
+  /// Create the alert handling without a GPIO pin, the alert is reported with [SmbusAlert::signal]
+  pub fn new() -> Self {
+    SmbusAlert {
+      pin: None,
+      pin_num: 0,
+      pending: Arc::new(AtomicBool::new(false)),
+      handlers: Vec::new(),
+      unhandled: None,
+    }
+  }
+
+  /// Bind the SMBALERT# line to the GPIO pin. The pin is configured as input with the pull-up enabled and its
+  /// falling edge marks an alert as pending. Dropping the alert handling unregisters the interrupt handler and
+  /// frees the pin again.
+  pub fn with_pin(pin: u32) -> I2cResult<Self> {
+    let mut alert = Self::new();
+    let pending = alert.pending.clone();
+    let line = GPIO.with_mut(|gpio| {
+      let line = gpio
+        .get_pin(pin)
+        .map_err(|_| I2cError::PinUnavailable { pin })?
+        .into_pud_up()
+        .into_input();
+      gpio.register_interrupt_handler(&line, GpioEvent::FallingEdge, move || {
+        pending.store(true, Ordering::Release);
+      });
+      Ok(line)
+    })?;
+    alert.pin = Some(line);
+    alert.pin_num = pin;
+    Ok(alert)
+  }
+
+  /// Register the handler of the alerts signalled by the device at the given address, replacing a handler
+  /// registered before
+  pub fn on<F>(&mut self, addr: u8, handler: F) -> I2cResult<()>
+  where
+    F: FnMut(&Smbus<'_, B>, SevenBitAddress) + 'static,
+  {
+    let addr = SevenBitAddress::new(addr)?;
+    self.handlers.retain(|(registered, _)| *registered != addr);
+    self.handlers.push((addr, Box::new(handler)));
+    Ok(())
+  }
+
+  /// Register the handler of the alerts signalled by devices without a handler of their own
+  pub fn on_unhandled<F>(&mut self, handler: F)
+  where
+    F: FnMut(&Smbus<'_, B>, SevenBitAddress) + 'static,
+  {
+    self.unhandled = Some(Box::new(handler));
+  }
+
+  /// Mark an alert as pending, e.g. from the interrupt handler of a SMBALERT# line not bound by this alert handling
+  pub fn signal(&self) {
+    self.pending.store(true, Ordering::Release);
+  }
+
+  /// Whether an alert has been signalled or the SMBALERT# line is still held low
+  pub fn is_pending(&self) -> bool {
+    self.pending.load(Ordering::Acquire) || self.pin.as_ref().is_some_and(|pin| pin.is_low())
+  }
+
+  /// Handle a pending alert: read the addresses of all alerting devices from the Alert Response Address and call the
+  /// handler of each of them. Returns the addresses of the devices that signalled an alert.
+  /// # Example
+  /// ```
+  /// # use ruspiro_i2c::I2cImpl;
+  /// # use ruspiro_i2c::backend::sim::*;
+  /// # use ruspiro_i2c::smbus::{SmbusAlert, ALERT_RESPONSE_ADDRESS};
+  /// # use std::{cell::Cell, rc::Rc};
+  /// // the devices 0x2A and 0x4C responding to the Alert Response Address in the order they win the arbitration
+  /// struct AlertResponse(Vec<u8>);
+  /// impl SimDevice for AlertResponse {
+  ///     fn start(&mut self, _read: bool) -> bool { !self.0.is_empty() }
+  ///     fn write(&mut self, _data: u8) -> bool { false }
+  ///     fn read(&mut self) -> u8 { self.0.remove(0) << 1 }
+  /// }
+  /// let bsc = SimulatedBsc::new();
+  /// bsc.attach(ALERT_RESPONSE_ADDRESS, AlertResponse(vec![0x2A, 0x4C]));
+  /// let mut i2c = I2cImpl::with_backend(bsc);
+  /// i2c.initialize(250_000_000, true).unwrap();
+  ///
+  /// let alerts = Rc::new(Cell::new(0));
+  /// let mut alert = SmbusAlert::new();
+  /// let counter = alerts.clone();
+  /// alert.on(0x4C, move |_, _| counter.set(counter.get() + 1)).unwrap();
+  /// alert.signal();
+  /// let handled = alert.handle(&i2c.smbus()).unwrap();
+  /// assert_eq!(handled.len(), 2);
+  /// assert_eq!(alerts.get(), 1);
+  /// assert!(!alert.is_pending());
+  /// ```
+  pub fn handle(&mut self, smbus: &Smbus<'_, B>) -> I2cResult<Vec<SevenBitAddress>> {
+    if !self.is_pending() {
+      return Ok(Vec::new());
+    }
+    self.pending.store(false, Ordering::Release);
+    let alerts = smbus.alert_responses()?;
+    for &addr in alerts.iter() {
+      let handler = self
+        .handlers
+        .iter_mut()
+        .find(|(registered, _)| *registered == addr)
+        .map(|(_, handler)| handler)
+        .or(self.unhandled.as_mut());
+      if let Some(handler) = handler {
+        handler(smbus, addr);
+      }
+    }
+    Ok(alerts)
+  }
+}
+
+impl<B: BscBackend> Default for SmbusAlert<B> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<B> Drop for SmbusAlert<B> {
+  fn drop(&mut self) {
+    if let Some(pin) = self.pin.take() {
+      // the handler must not fire for a pin that is handed out again
+      GPIO.with_mut(|gpio| {
+        gpio.unregister_interrupt_handler(&pin, GpioEvent::FallingEdge);
+        gpio.free_pin(self.pin_num);
+      });
+    }
+  }
+}